@@ -1,5 +1,15 @@
 pub const CELL_SIZE: u16 = 16;
+/// One screen of 16x16 tiles in pixels, the unit of the room size and map position.
+pub const SCREEN_SIZE: u32 = CELL_SIZE as u32 * 16;
 
 pub const BTS_SLOPE_FLIP_MASK: u8 = 0b1100_0000;
 pub const BTS_SLOPE_YELLOW_MASK: u8 = 0b0010_0000;
 pub const BTS_SLOPE_TYPE_MASK: u8 = 0b0001_1111;
+
+pub const ROOM_HEADER_SIZE: usize = 0x0F;
+
+pub const LEVEL_TILE_MASK: u16 = 0b0000_0011_1111_1111;
+pub const LEVEL_FLIP_MASK: u16 = 0b0000_1100_0000_0000;
+pub const LEVEL_HFLIP_MASK: u16 = 0b0000_0100_0000_0000;
+pub const LEVEL_VFLIP_MASK: u16 = 0b0000_1000_0000_0000;
+pub const LEVEL_BLOCK_TYPE_MASK: u16 = 0b1111_0000_0000_0000;
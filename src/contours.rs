@@ -0,0 +1,161 @@
+use image::{GrayImage, Luma};
+use imageproc::{
+    contours::{find_contours, BorderType},
+    drawing::draw_polygon_mut,
+};
+
+use crate::{
+    constants::CELL_SIZE,
+    shapes::Polygon,
+    types::{BlockType, Room},
+};
+
+/// Empty pixels kept around the collision mask.
+///
+/// `find_contours` misses shapes touching the left edge of the image, so the
+/// mask is padded and the contours moved back afterwards.
+const MASK_MARGIN: u32 = 1;
+
+/// Whether a block type stops the player, slopes only on the solid part of their shape.
+///
+/// Doors are the transition area in front of the door cap and are left out.
+pub fn is_collision(block_type: BlockType) -> bool {
+    matches!(
+        block_type,
+        BlockType::Slope
+            | BlockType::Solid
+            | BlockType::Treadmill
+            | BlockType::Spike
+            | BlockType::Crumble
+            | BlockType::Shot
+            | BlockType::Grapple
+            | BlockType::Bomb
+    )
+}
+
+/// Unions the collision of every cell into room-wide contours.
+///
+/// Outer contours wind clockwise on screen and holes counter-clockwise, so
+/// `Polygon::signed_area` is positive for outer contours and negative for
+/// holes. Points are the centers of the border pixels, in the same inclusive
+/// pixel coordinates as the per-cell polygons.
+pub fn collision_contours(room: &Room) -> Vec<Polygon> {
+    collision_contours_with_tolerance(room, 0.0)
+}
+
+/// `collision_contours`, simplified with `Polygon::symplify_with_tolerance`.
+pub fn collision_contours_with_tolerance(room: &Room, tolerance: f32) -> Vec<Polygon> {
+    let width = room.get_room_width_tiles() as u32 * CELL_SIZE as u32;
+    let height = room.get_room_height_tiles() as u32 * CELL_SIZE as u32;
+
+    // the per-cell shapes are pixel exact, so the union is exact on the pixel grid
+    let mut mask = GrayImage::new(width + 2 * MASK_MARGIN, height + 2 * MASK_MARGIN);
+    for cell in &room.cells {
+        if !is_collision(cell.effective_block_type()) {
+            continue;
+        }
+        for mut polygon in cell.polygons() {
+            polygon.translate(MASK_MARGIN as f32, MASK_MARGIN as f32);
+            draw_polygon_mut(&mut mask, &polygon.points, Luma([255]));
+        }
+    }
+
+    find_contours::<i32>(&mask)
+        .into_iter()
+        .filter_map(|contour| {
+            let mut polygon = Polygon::new(contour.points);
+            polygon.translate(-(MASK_MARGIN as f32), -(MASK_MARGIN as f32));
+            polygon.symplify_with_tolerance(tolerance);
+            if polygon.points.len() < 3 {
+                return None;
+            }
+
+            let is_hole = contour.border_type == BorderType::Hole;
+            if (polygon.signed_area() < 0.0) != is_hole {
+                polygon.points.reverse();
+            }
+
+            Some(polygon)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::room_from;
+
+    #[test]
+    fn solid_cells_merge_into_one_contour() {
+        // a 4x2 block of solid cells
+        let room = room_from(|x, y| match (x, y) {
+            (2..=5, 3..=4) => (0x8000, 0),
+            _ => (0, 0),
+        });
+
+        let contours = collision_contours(&room);
+        assert_eq!(contours.len(), 1);
+        assert_eq!(contours[0].points.len(), 4);
+        assert!(contours[0].signed_area() > 0.0);
+    }
+
+    #[test]
+    fn enclosed_air_becomes_a_hole() {
+        // a 3x3 ring of solid cells around one air cell
+        let room = room_from(|x, y| match (x, y) {
+            (5, 5) => (0, 0),
+            (4..=6, 4..=6) => (0x8000, 0),
+            _ => (0, 0),
+        });
+
+        let contours = collision_contours(&room);
+        assert_eq!(contours.len(), 2);
+        assert_eq!(contours.iter().filter(|c| c.signed_area() < 0.0).count(), 1);
+    }
+
+    #[test]
+    fn shapes_touching_the_room_edge_are_found() {
+        // solid walls on the left and right and a floor, leaving an open top
+        let room = room_from(|x, y| match (x, y) {
+            (0 | 15, _) | (_, 15) => (0x8000, 0),
+            _ => (0, 0),
+        });
+
+        let contours = collision_contours(&room);
+        assert_eq!(contours.len(), 1);
+        assert!(contours[0].signed_area() > 0.0);
+        assert!(contours[0].points.iter().any(|point| point.x == 0));
+    }
+
+    #[test]
+    fn margin_does_not_shift_contours() {
+        let room = room_from(|x, y| match (x, y) {
+            (0 | 15, 0 | 15) => (0x8000, 0),
+            _ => (0, 0),
+        });
+
+        let mut bounds: Vec<(i32, i32, i32, i32)> = collision_contours(&room)
+            .iter()
+            .map(|contour| {
+                let xs = contour.points.iter().map(|point| point.x);
+                let ys = contour.points.iter().map(|point| point.y);
+                (
+                    xs.clone().min().unwrap(),
+                    ys.clone().min().unwrap(),
+                    xs.max().unwrap(),
+                    ys.max().unwrap(),
+                )
+            })
+            .collect();
+        bounds.sort();
+        assert_eq!(
+            bounds,
+            [
+                (0, 0, 15, 15),
+                (0, 240, 15, 255),
+                (240, 0, 255, 15),
+                (240, 240, 255, 255)
+            ]
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use std::fmt;
+
+/// Errors returned by `Room::try_from_bytes` when a `.room` file is malformed.
+///
+/// Every variant carries the byte offsets into the input that caused the
+/// failure, so a bad file can be inspected with a hex editor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoomParseError {
+    /// The input is shorter than the 15 byte room header.
+    ShortHeader { len: usize, expected: usize },
+    /// The header passed to `Room::try_from_compressed` is longer than 15 bytes.
+    LongHeader { len: usize, expected: usize },
+    /// The header describes a room with a width or height of zero screens.
+    ZeroSized { width: u8, height: u8 },
+    /// The level data (2 bytes per tile) ends before `expected_end`.
+    TruncatedLevelData {
+        offset: usize,
+        expected_end: usize,
+        actual_end: usize,
+    },
+    /// The BTS data (1 byte per tile) ends before `expected_end`.
+    TruncatedBtsData {
+        offset: usize,
+        expected_end: usize,
+        actual_end: usize,
+    },
+    /// The bytes after the BTS data are neither a full layer 2 block nor zero padding.
+    TrailingDataMismatch {
+        offset: usize,
+        len: usize,
+        expected: usize,
+    },
+    /// The compressed level data passed to `Room::try_from_compressed` is malformed.
+    Compression(LzError),
+}
+
+impl fmt::Display for RoomParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoomParseError::ShortHeader { len, expected } => write!(
+                f,
+                "room header is too short: got {:#X} bytes, expected {:#X}",
+                len, expected
+            ),
+            RoomParseError::LongHeader { len, expected } => write!(
+                f,
+                "room header is too long: got {:#X} bytes, expected {:#X}",
+                len, expected
+            ),
+            RoomParseError::ZeroSized { width, height } => write!(
+                f,
+                "room has zero size: width {} screens, height {} screens",
+                width, height
+            ),
+            RoomParseError::TruncatedLevelData {
+                offset,
+                expected_end,
+                actual_end,
+            } => write!(
+                f,
+                "level data starting at {:#X} is truncated: expected end at {:#X}, data ends at {:#X}",
+                offset, expected_end, actual_end
+            ),
+            RoomParseError::TruncatedBtsData {
+                offset,
+                expected_end,
+                actual_end,
+            } => write!(
+                f,
+                "bts data starting at {:#X} is truncated: expected end at {:#X}, data ends at {:#X}",
+                offset, expected_end, actual_end
+            ),
+            RoomParseError::TrailingDataMismatch {
+                offset,
+                len,
+                expected,
+            } => write!(
+                f,
+                "unexpected {:#X} trailing bytes at {:#X}: expected zero padding or {:#X} bytes of layer 2",
+                len, offset, expected
+            ),
+            RoomParseError::Compression(e) => write!(f, "invalid level data: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RoomParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RoomParseError::Compression(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Errors returned by `lz::decompress` for a malformed compressed blob.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LzError {
+    /// The input ends inside the command starting at `offset`, or before the end marker.
+    UnexpectedEnd { offset: usize },
+    /// A dictionary copy at `offset` reads output that wasn't written yet.
+    InvalidCopy {
+        offset: usize,
+        source: usize,
+        output_len: usize,
+    },
+}
+
+impl fmt::Display for LzError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LzError::UnexpectedEnd { offset } => {
+                write!(f, "compressed data ends in the command at {:#X}", offset)
+            }
+            LzError::InvalidCopy {
+                offset,
+                source,
+                output_len,
+            } => write!(
+                f,
+                "copy at {:#X} reads from {:#X}, only {:#X} bytes were decompressed",
+                offset, source, output_len
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LzError {}
+
+/// Errors returned when loading a theme for `RenderStyle`.
+#[derive(Debug)]
+pub enum ThemeError {
+    /// The theme file could not be read.
+    Io(std::io::Error),
+    /// A line of the theme could not be parsed, `line` starts at 1.
+    InvalidLine { line: usize, message: String },
+}
+
+impl fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThemeError::Io(e) => write!(f, "cannot read theme: {}", e),
+            ThemeError::InvalidLine { line, message } => {
+                write!(f, "invalid theme line {}: {}", line, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ThemeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ThemeError::Io(e) => Some(e),
+            ThemeError::InvalidLine { .. } => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ThemeError {
+    fn from(e: std::io::Error) -> Self {
+        ThemeError::Io(e)
+    }
+}
+
+/// Errors returned by `Rom` when reading the ROM image or the rooms in it.
+///
+/// Addresses are SNES LoROM addresses like `0x8F91F8`, the way room headers
+/// are usually listed in documentation.
+#[derive(Debug)]
+pub enum RomError {
+    /// The ROM file could not be read.
+    Io(std::io::Error),
+    /// The image is neither a multiple of 32 KiB nor one with a 512 byte copier header.
+    InvalidSize { len: usize },
+    /// The address is not mapped by LoROM or lies past the end of the image.
+    AddressOutOfRange { address: u32 },
+    /// The state list of the room header at `room` has an unknown condition at `address`.
+    UnknownStateCondition { room: u32, address: u32, code: u16 },
+    /// The compressed level data at `address` is malformed.
+    Decompression { address: u32, error: LzError },
+    /// The level data of the room header at `room` doesn't describe a valid room.
+    Room { room: u32, error: RoomParseError },
+    /// The state list of the room header at `room` doesn't end with a default state.
+    NoDefaultState { room: u32 },
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RomError::Io(e) => write!(f, "cannot read rom: {}", e),
+            RomError::InvalidSize { len } => write!(
+                f,
+                "rom size {:#X} is not a multiple of 0x8000, with or without a 0x200 byte header",
+                len
+            ),
+            RomError::AddressOutOfRange { address } => {
+                write!(f, "address ${:06X} is outside of the rom", address)
+            }
+            RomError::UnknownStateCondition {
+                room,
+                address,
+                code,
+            } => write!(
+                f,
+                "room ${:06X} has an unknown state condition {:04X} at ${:06X}",
+                room, code, address
+            ),
+            RomError::Decompression { address, error } => {
+                write!(f, "level data at ${:06X}: {}", address, error)
+            }
+            RomError::Room { room, error } => write!(f, "room ${:06X}: {}", room, error),
+            RomError::NoDefaultState { room } => {
+                write!(f, "room ${:06X} has no default state", room)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RomError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RomError::Io(e) => Some(e),
+            RomError::Decompression { error, .. } => Some(error),
+            RomError::Room { error, .. } => Some(error),
+            RomError::InvalidSize { .. }
+            | RomError::AddressOutOfRange { .. }
+            | RomError::UnknownStateCondition { .. }
+            | RomError::NoDefaultState { .. } => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for RomError {
+    fn from(e: std::io::Error) -> Self {
+        RomError::Io(e)
+    }
+}
@@ -0,0 +1,449 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt::Write,
+    io,
+    path::Path,
+};
+
+use image::Rgba;
+
+use crate::{
+    constants::{CELL_SIZE, SCREEN_SIZE},
+    render::RenderStyle,
+    types::{BlockType, Room},
+};
+
+const JSON_VERSION: &str = "1.5.3";
+
+// every area map is 64x32 screens, areas are stacked vertically in the world
+const AREA_HEIGHT: u32 = 32;
+
+const COLLISION_LAYER_UID: u32 = 1;
+const DOORS_LAYER_UID: u32 = 2;
+const DOOR_ENTITY_UID: u32 = 3;
+const DOOR_INDEX_FIELD_UID: u32 = 4;
+const FIRST_LEVEL_UID: u32 = 5;
+
+const DOOR_INDEX_FIELD: &str = "door_index";
+
+const SOLID_COLOR: Rgba<u8> = Rgba([0, 255, 0, 255]);
+const COPY_COLOR: Rgba<u8> = Rgba([128, 128, 128, 255]);
+const DOOR_COLOR: Rgba<u8> = Rgba([0, 128, 255, 255]);
+
+/// Renders the rooms as an LDtk project with one level per room.
+///
+/// Levels are placed by `map_x` and `map_y` in a free world layout, with
+/// the area maps stacked from top to bottom in `AreaIndex` order. Every level
+/// has a `Collision` IntGrid layer holding the effective `BlockType` of each
+/// cell (air is empty) and a `Doors` entity layer with one `Door` per group of
+/// adjacent door cells.
+pub fn render_ldtk(rooms: &[Room]) -> String {
+    let mut json = String::new();
+    writeln!(json, "{{").unwrap();
+    writeln!(
+        json,
+        r#"  "__header__": {{ "fileType": "LDtk Project JSON", "app": "LDtk", "doc": "https://ldtk.io/json", "schema": "https://ldtk.io/files/JSON_SCHEMA.json", "appAuthor": "Sebastien 'deepnight' Benard", "appVersion": "{}", "url": "https://ldtk.io" }},"#,
+        JSON_VERSION
+    )
+    .unwrap();
+    writeln!(json, r#"  "iid": "{}","#, iid(0, 0)).unwrap();
+    writeln!(json, r#"  "jsonVersion": "{}","#, JSON_VERSION).unwrap();
+    writeln!(
+        json,
+        r#"  "nextUid": {},"#,
+        FIRST_LEVEL_UID + rooms.len() as u32
+    )
+    .unwrap();
+    writeln!(json, r#"  "identifierStyle": "Capitalize","#).unwrap();
+    writeln!(json, r#"  "worldLayout": "Free","#).unwrap();
+    writeln!(json, r#"  "worldGridWidth": {},"#, SCREEN_SIZE).unwrap();
+    writeln!(json, r#"  "worldGridHeight": {},"#, SCREEN_SIZE).unwrap();
+    writeln!(json, r#"  "defaultLevelWidth": {},"#, SCREEN_SIZE).unwrap();
+    writeln!(json, r#"  "defaultLevelHeight": {},"#, SCREEN_SIZE).unwrap();
+    writeln!(json, r#"  "defaultGridSize": {},"#, CELL_SIZE).unwrap();
+    writeln!(json, r#"  "defaultEntityWidth": {},"#, CELL_SIZE).unwrap();
+    writeln!(json, r#"  "defaultEntityHeight": {},"#, CELL_SIZE).unwrap();
+    writeln!(json, r##"  "bgColor": "#000000","##).unwrap();
+    writeln!(json, r##"  "defaultLevelBgColor": "#000000","##).unwrap();
+    writeln!(json, r#"  "externalLevels": false,"#).unwrap();
+    writeln!(json, r#"  "simplifiedExport": false,"#).unwrap();
+    writeln!(json, r#"  "minifyJson": false,"#).unwrap();
+    writeln!(json, r#"  "flags": [],"#).unwrap();
+    writeln!(json, r#"  "worlds": [],"#).unwrap();
+    writeln!(json, r#"  "toc": [],"#).unwrap();
+    write_defs(&mut json);
+
+    writeln!(json, r#"  "levels": ["#).unwrap();
+    let mut identifiers = HashSet::new();
+    for (i, room) in rooms.iter().enumerate() {
+        let mut identifier = capitalize(&format!(
+            "{}_{:02X}",
+            room.area_index().name(),
+            room.room_index()
+        ));
+        while !identifiers.insert(identifier.clone()) {
+            identifier.push('_');
+        }
+
+        write_level(&mut json, room, FIRST_LEVEL_UID + i as u32, &identifier);
+        json.push_str(if i + 1 < rooms.len() { ",\n" } else { "\n" });
+    }
+    writeln!(json, "  ]").unwrap();
+    writeln!(json, "}}").unwrap();
+
+    json
+}
+
+pub fn save_ldtk<P: AsRef<Path>>(rooms: &[Room], path: P) -> io::Result<()> {
+    let path = path.as_ref();
+
+    // check if the folder exists
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() && !parent.exists() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+
+    std::fs::write(path, render_ldtk(rooms))
+}
+
+fn write_defs(json: &mut String) {
+    let values: Vec<String> = BlockType::ALL
+        .into_iter()
+        .filter(|block_type| *block_type != BlockType::Air)
+        .map(|block_type| {
+            format!(
+                r#"{{ "value": {}, "identifier": "{}", "color": "{}", "tile": null, "groupUid": 0 }}"#,
+                block_type as u8,
+                capitalize(block_type.name()),
+                hex_color(block_color(block_type))
+            )
+        })
+        .collect();
+
+    writeln!(json, r#"  "defs": {{"#).unwrap();
+    writeln!(json, r#"    "layers": ["#).unwrap();
+    writeln!(
+        json,
+        r#"      {{ "__type": "Entities", "identifier": "Doors", "type": "Entities", "uid": {}, "doc": null, "uiColor": null, "gridSize": {}, "guideGridWid": 0, "guideGridHei": 0, "displayOpacity": 1, "inactiveOpacity": 0.6, "hideInList": false, "hideFieldsWhenInactive": true, "canSelectWhenInactive": true, "renderInWorldView": true, "pxOffsetX": 0, "pxOffsetY": 0, "parallaxFactorX": 0, "parallaxFactorY": 0, "parallaxScaling": true, "requiredTags": [], "excludedTags": [], "autoTilesKilledByOtherLayerUid": null, "uiFilterTags": [], "useAsyncRender": false, "intGridValues": [], "intGridValuesGroups": [], "autoRuleGroups": [], "autoSourceLayerDefUid": null, "tilesetDefUid": null, "tilePivotX": 0, "tilePivotY": 0, "biomeFieldUid": null }},"#,
+        DOORS_LAYER_UID, CELL_SIZE
+    )
+    .unwrap();
+    writeln!(
+        json,
+        r#"      {{ "__type": "IntGrid", "identifier": "Collision", "type": "IntGrid", "uid": {}, "doc": null, "uiColor": null, "gridSize": {}, "guideGridWid": 0, "guideGridHei": 0, "displayOpacity": 1, "inactiveOpacity": 1, "hideInList": false, "hideFieldsWhenInactive": true, "canSelectWhenInactive": true, "renderInWorldView": true, "pxOffsetX": 0, "pxOffsetY": 0, "parallaxFactorX": 0, "parallaxFactorY": 0, "parallaxScaling": true, "requiredTags": [], "excludedTags": [], "autoTilesKilledByOtherLayerUid": null, "uiFilterTags": [], "useAsyncRender": false, "intGridValues": [{}], "intGridValuesGroups": [], "autoRuleGroups": [], "autoSourceLayerDefUid": null, "tilesetDefUid": null, "tilePivotX": 0, "tilePivotY": 0, "biomeFieldUid": null }}"#,
+        COLLISION_LAYER_UID,
+        CELL_SIZE,
+        values.join(", ")
+    )
+    .unwrap();
+    writeln!(json, "    ],").unwrap();
+
+    writeln!(json, r#"    "entities": ["#).unwrap();
+    writeln!(
+        json,
+        r#"      {{ "identifier": "Door", "uid": {}, "tags": [], "exportToToc": false, "allowOutOfBounds": false, "doc": null, "width": {}, "height": {}, "resizableX": true, "resizableY": true, "minWidth": null, "maxWidth": null, "minHeight": null, "maxHeight": null, "keepAspectRatio": false, "tileOpacity": 1, "fillOpacity": 0.5, "lineOpacity": 1, "hollow": false, "color": "{}", "renderMode": "Rectangle", "showName": true, "tilesetId": null, "tileRenderMode": "FitInside", "tileRect": null, "uiTileRect": null, "nineSliceBorders": [], "maxCount": 0, "limitScope": "PerLevel", "limitBehavior": "MoveLastOne", "pivotX": 0, "pivotY": 0, "fieldDefs": ["#,
+        DOOR_ENTITY_UID,
+        CELL_SIZE,
+        CELL_SIZE,
+        hex_color(DOOR_COLOR)
+    )
+    .unwrap();
+    writeln!(
+        json,
+        r#"        {{ "identifier": "{}", "doc": null, "__type": "Int", "uid": {}, "type": "F_Int", "isArray": false, "canBeNull": false, "arrayMinLength": null, "arrayMaxLength": null, "editorDisplayMode": "ValueOnly", "editorDisplayScale": 1, "editorDisplayPos": "Above", "editorLinkStyle": "StraightArrow", "editorDisplayColor": null, "editorAlwaysShow": false, "editorShowInWorld": true, "editorCutLongValues": true, "editorTextSuffix": null, "editorTextPrefix": null, "useForSmartColor": false, "exportToToc": false, "searchable": false, "min": null, "max": null, "regex": null, "acceptFileTypes": null, "defaultOverride": null, "textLanguageMode": null, "symmetricalRef": false, "autoChainRef": true, "allowOutOfLevelRef": true, "allowedRefs": "Any", "allowedRefsEntityUid": null, "allowedRefTags": [], "tilesetUid": null }}"#,
+        capitalize(DOOR_INDEX_FIELD),
+        DOOR_INDEX_FIELD_UID
+    )
+    .unwrap();
+    writeln!(json, "      ] }}").unwrap();
+    writeln!(json, "    ],").unwrap();
+
+    writeln!(json, r#"    "tilesets": [],"#).unwrap();
+    writeln!(json, r#"    "enums": [],"#).unwrap();
+    writeln!(json, r#"    "externalEnums": [],"#).unwrap();
+    writeln!(json, r#"    "levelFields": []"#).unwrap();
+    writeln!(json, "  }},").unwrap();
+}
+
+fn write_level(json: &mut String, room: &Room, uid: u32, identifier: &str) {
+    let width = room.get_room_width_tiles() as u32;
+    let height = room.get_room_height_tiles() as u32;
+    let world_x = room.map_x() as u32 * SCREEN_SIZE;
+    let world_y =
+        (room.area_index().index() as u32 * AREA_HEIGHT + room.map_y() as u32) * SCREEN_SIZE;
+
+    writeln!(json, "    {{").unwrap();
+    writeln!(json, r#"      "identifier": "{}","#, identifier).unwrap();
+    writeln!(json, r#"      "iid": "{}","#, iid(1, uid as u64)).unwrap();
+    writeln!(json, r#"      "uid": {},"#, uid).unwrap();
+    writeln!(json, r#"      "worldX": {},"#, world_x).unwrap();
+    writeln!(json, r#"      "worldY": {},"#, world_y).unwrap();
+    writeln!(json, r#"      "worldDepth": 0,"#).unwrap();
+    writeln!(json, r#"      "pxWid": {},"#, width * CELL_SIZE as u32).unwrap();
+    writeln!(json, r#"      "pxHei": {},"#, height * CELL_SIZE as u32).unwrap();
+    writeln!(json, r##"      "__bgColor": "#000000","##).unwrap();
+    writeln!(json, r#"      "bgColor": null,"#).unwrap();
+    writeln!(json, r#"      "useAutoIdentifier": false,"#).unwrap();
+    writeln!(json, r#"      "bgRelPath": null,"#).unwrap();
+    writeln!(json, r#"      "bgPos": null,"#).unwrap();
+    writeln!(json, r#"      "bgPivotX": 0.5,"#).unwrap();
+    writeln!(json, r#"      "bgPivotY": 0.5,"#).unwrap();
+    writeln!(json, r##"      "__smartColor": "#ADADB5","##).unwrap();
+    writeln!(json, r#"      "__bgPos": null,"#).unwrap();
+    writeln!(json, r#"      "externalRelPath": null,"#).unwrap();
+    writeln!(json, r#"      "fieldInstances": [],"#).unwrap();
+    writeln!(json, r#"      "__neighbours": [],"#).unwrap();
+    writeln!(json, r#"      "layerInstances": ["#).unwrap();
+
+    // doors, the top layer comes first
+    let doors: Vec<String> = door_groups(room)
+        .into_iter()
+        .enumerate()
+        .map(|(i, (x, y, w, h, door_index))| {
+            let px = x * CELL_SIZE as u32;
+            let py = y * CELL_SIZE as u32;
+            format!(
+                r#"{{ "__identifier": "Door", "__grid": [{}, {}], "__pivot": [0, 0], "__tags": [], "__tile": null, "__smartColor": "{}", "__worldX": {}, "__worldY": {}, "iid": "{}", "width": {}, "height": {}, "defUid": {}, "px": [{}, {}], "fieldInstances": [{{ "__identifier": "{}", "__type": "Int", "__value": {}, "__tile": null, "defUid": {}, "realEditorValues": [{{ "id": "V_Int", "params": [{}] }}] }}] }}"#,
+                x,
+                y,
+                hex_color(DOOR_COLOR),
+                world_x + px,
+                world_y + py,
+                iid(3, (uid as u64) << 16 | i as u64),
+                w * CELL_SIZE as u32,
+                h * CELL_SIZE as u32,
+                DOOR_ENTITY_UID,
+                px,
+                py,
+                capitalize(DOOR_INDEX_FIELD),
+                door_index,
+                DOOR_INDEX_FIELD_UID,
+                door_index
+            )
+        })
+        .collect();
+    write_layer_instance(json, room, uid, "Doors", "Entities", DOORS_LAYER_UID);
+    writeln!(
+        json,
+        r#"          "intGridCsv": [], "entityInstances": [{}] }},"#,
+        doors.join(", ")
+    )
+    .unwrap();
+
+    let values: Vec<String> = room
+        .cells
+        .iter()
+        .map(|cell| (cell.effective_block_type() as u8).to_string())
+        .collect();
+    write_layer_instance(json, room, uid, "Collision", "IntGrid", COLLISION_LAYER_UID);
+    writeln!(
+        json,
+        r#"          "intGridCsv": [{}], "entityInstances": [] }}"#,
+        values.join(",")
+    )
+    .unwrap();
+
+    writeln!(json, "      ]").unwrap();
+    write!(json, "    }}").unwrap();
+}
+
+// everything but the layer contents, which the caller appends
+fn write_layer_instance(
+    json: &mut String,
+    room: &Room,
+    level_uid: u32,
+    identifier: &str,
+    kind: &str,
+    layer_uid: u32,
+) {
+    writeln!(
+        json,
+        r#"        {{ "__identifier": "{}", "__type": "{}", "__cWid": {}, "__cHei": {}, "__gridSize": {}, "__opacity": 1, "__pxTotalOffsetX": 0, "__pxTotalOffsetY": 0, "__tilesetDefUid": null, "__tilesetRelPath": null, "iid": "{}", "levelId": {}, "layerDefUid": {}, "pxOffsetX": 0, "pxOffsetY": 0, "visible": true, "optionalRules": [], "autoLayerTiles": [], "seed": 0, "overrideTilesetUid": null, "gridTiles": [],"#,
+        identifier,
+        kind,
+        room.get_room_width_tiles(),
+        room.get_room_height_tiles(),
+        CELL_SIZE,
+        iid(2, (level_uid as u64) << 8 | layer_uid as u64),
+        level_uid,
+        layer_uid
+    )
+    .unwrap();
+}
+
+// bounding boxes of 4-connected door cells, as (x, y, width, height, bts of the first cell)
+fn door_groups(room: &Room) -> Vec<(u32, u32, u32, u32, u8)> {
+    let width = room.get_room_width_tiles() as usize;
+    let height = room.get_room_height_tiles() as usize;
+    let is_door = |index: usize| room.cells[index].effective_block_type() == BlockType::Door;
+
+    let mut visited = vec![false; room.cells.len()];
+    let mut groups = Vec::new();
+    for start in 0..room.cells.len() {
+        if visited[start] || !is_door(start) {
+            continue;
+        }
+
+        let (mut min_x, mut min_y) = (start % width, start / width);
+        let (mut max_x, mut max_y) = (min_x, min_y);
+        let mut queue = VecDeque::from([start]);
+        visited[start] = true;
+        while let Some(index) = queue.pop_front() {
+            let (x, y) = (index % width, index / width);
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+
+            let neighbors = [
+                (x > 0).then(|| index - 1),
+                (x + 1 < width).then(|| index + 1),
+                (y > 0).then(|| index - width),
+                (y + 1 < height).then(|| index + width),
+            ];
+            for neighbor in neighbors.into_iter().flatten() {
+                if !visited[neighbor] && is_door(neighbor) {
+                    visited[neighbor] = true;
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        groups.push((
+            min_x as u32,
+            min_y as u32,
+            (max_x - min_x + 1) as u32,
+            (max_y - min_y + 1) as u32,
+            room.cells[start].effective_bts(),
+        ));
+    }
+
+    groups
+}
+
+fn block_color(block_type: BlockType) -> Rgba<u8> {
+    match block_type {
+        BlockType::Solid | BlockType::Slope => SOLID_COLOR,
+        _ => RenderStyle::default()
+            .block(block_type)
+            .map_or(COPY_COLOR, |style| style.color),
+    }
+}
+
+// the project uses the "Capitalize" identifier style
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn hex_color(color: Rgba<u8>) -> String {
+    format!("#{:02X}{:02X}{:02X}", color[0], color[1], color[2])
+}
+
+// ldtk only needs the iids to be unique within the project, `kind` keeps
+// projects, levels, layers and entities apart
+fn iid(kind: u16, id: u64) -> String {
+    format!("6d616d61-{:04x}-4000-8000-{:012x}", kind, id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::room_from;
+
+    #[test]
+    fn adjacent_door_cells_become_one_entity() {
+        // a door on the left wall and one on the right
+        let room = room_from(|x, y| match (x, y) {
+            (0, 6..=9) => (0x9000, 0),
+            (15, 6..=9) => (0x9000, 1),
+            _ => (0, 0),
+        });
+
+        assert_eq!(door_groups(&room), vec![(0, 6, 1, 4, 0), (15, 6, 1, 4, 1)]);
+        assert_eq!(
+            render_ldtk(&[room])
+                .matches(r#""__identifier": "Door""#)
+                .count(),
+            2
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn project_is_valid_ldtk_json() {
+        let room = room_from(|x, y| match (x, y) {
+            (0, 6..=9) => (0x9000, 2),
+            (_, 15) => (0x8000, 0),
+            _ => (0, 0),
+        });
+        let json: serde_json::Value = serde_json::from_str(&render_ldtk(&[room])).unwrap();
+
+        assert_eq!(json["__header__"]["fileType"], "LDtk Project JSON");
+        assert_eq!(json["jsonVersion"], JSON_VERSION);
+        assert_eq!(json["identifierStyle"], "Capitalize");
+        assert_eq!(json["worldGridWidth"], SCREEN_SIZE);
+
+        let layers = json["defs"]["layers"].as_array().unwrap();
+        assert_eq!(layers[0]["identifier"], "Doors");
+        assert_eq!(layers[1]["identifier"], "Collision");
+        let values = layers[1]["intGridValues"].as_array().unwrap();
+        assert_eq!(values.len(), BlockType::ALL.len() - 1);
+        assert!(values
+            .iter()
+            .any(|value| value["value"] == 8 && value["identifier"] == "Solid"));
+        let door = &json["defs"]["entities"][0];
+        assert_eq!(door["identifier"], "Door");
+        assert_eq!(door["fieldDefs"][0]["identifier"], "Door_index");
+
+        let level = &json["levels"][0];
+        assert_eq!(level["identifier"], "Crateria_00");
+        assert_eq!(level["uid"], FIRST_LEVEL_UID);
+        assert_eq!(
+            (level["pxWid"].as_u64(), level["pxHei"].as_u64()),
+            (Some(256), Some(256))
+        );
+
+        let instances = level["layerInstances"].as_array().unwrap();
+        assert_eq!(instances[0]["__identifier"], "Doors");
+        let entity = &instances[0]["entityInstances"][0];
+        assert_eq!(entity["__identifier"], "Door");
+        assert_eq!(entity["px"], serde_json::json!([0, 96]));
+        assert_eq!(entity["height"], 64);
+        assert_eq!(entity["fieldInstances"][0]["__identifier"], "Door_index");
+        assert_eq!(entity["fieldInstances"][0]["__value"], 2);
+
+        let collision = &instances[1];
+        assert_eq!(collision["__identifier"], "Collision");
+        assert_eq!(
+            (collision["__cWid"].as_u64(), collision["__cHei"].as_u64()),
+            (Some(16), Some(16))
+        );
+        let csv = collision["intGridCsv"].as_array().unwrap();
+        assert_eq!(csv.len(), 256);
+        assert_eq!(csv[255], 8);
+        assert_eq!(csv[16 * 6], 9);
+
+        // every identifier follows the project's identifier style
+        let mut stack = vec![&json];
+        while let Some(value) = stack.pop() {
+            match value {
+                serde_json::Value::Object(map) => {
+                    for (key, value) in map {
+                        if key == "identifier" || key == "__identifier" {
+                            let name = value.as_str().unwrap();
+                            assert!(name.starts_with(char::is_uppercase), "{}", name);
+                        }
+                        stack.push(value);
+                    }
+                }
+                serde_json::Value::Array(values) => stack.extend(values),
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,27 @@
+//! Parser and collision renderer for Super Metroid `.room` files and ROMs.
+//!
+//! The binary in `main.rs` is a thin wrapper around this crate, other tools
+//! can depend on it to read rooms and work with the collision polygons.
+
+pub mod constants;
+pub mod contours;
+pub mod error;
+pub mod ldtk;
+pub mod lz;
+pub mod render;
+pub mod rom;
+pub mod shapes;
+pub mod slope_runs;
+pub mod state;
+pub mod svg;
+pub mod tiled;
+pub mod types;
+pub mod world;
+
+pub use error::{LzError, RomError, RoomParseError, ThemeError};
+pub use render::{BlockStyle, Pattern, RenderStyle, Scale};
+pub use rom::Rom;
+pub use shapes::{vectors::Vector, Polygon, SlopeShape};
+pub use slope_runs::SlopeRun;
+pub use state::{Door, Enemy, Fx, Plm, PlmCategory, RoomState, StateCondition};
+pub use types::{AreaIndex, BlockType, Cell, Flip, Layer2Tile, Room, SlopeType, TreatAsSlopeType};
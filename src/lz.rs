@@ -0,0 +1,456 @@
+//! The LZ format the game uses for level data, tiles and tilemaps.
+//!
+//! A compressed blob is a list of commands ended by a `0xFF` byte. Every
+//! command starts with a header byte `CCCLLLLL`, where `C` is the command and
+//! `L` the length minus one. The command `0b111` marks a two byte header
+//! `111CCCLL LLLLLLLL` with a 10 bit length, for the same commands.
+
+use std::collections::HashMap;
+
+use crate::error::LzError;
+
+pub const END: u8 = 0xFF;
+
+/// The command types of the format.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Command {
+    /// Copy the next `length` bytes to the output.
+    DirectCopy = 0,
+    /// Repeat the next byte `length` times.
+    ByteFill,
+    /// Alternate the next two bytes for `length` bytes.
+    WordFill,
+    /// Write the next byte `length` times, adding one after every write.
+    IncrementFill,
+    /// Copy `length` bytes of the output starting at the 16 bit address that follows.
+    DictionaryCopy,
+    /// `DictionaryCopy` with every copied byte inverted.
+    DictionaryCopyInverted,
+    /// Copy `length` bytes of the output starting the next byte's value back
+    /// from the end of the output.
+    RelativeCopy,
+    /// `RelativeCopy` with every copied byte inverted.
+    RelativeCopyInverted,
+}
+
+impl From<u8> for Command {
+    fn from(value: u8) -> Self {
+        match value & 0x07 {
+            0 => Command::DirectCopy,
+            1 => Command::ByteFill,
+            2 => Command::WordFill,
+            3 => Command::IncrementFill,
+            4 => Command::DictionaryCopy,
+            5 => Command::DictionaryCopyInverted,
+            6 => Command::RelativeCopy,
+            _ => Command::RelativeCopyInverted,
+        }
+    }
+}
+
+/// Decompresses a blob, returning the data and the number of input bytes
+/// used including the end marker.
+pub fn decompress_with_len(input: &[u8]) -> Result<(Vec<u8>, usize), LzError> {
+    let mut output = Vec::new();
+    let mut pos = 0;
+
+    loop {
+        let offset = pos;
+        let next = |pos: &mut usize| -> Result<u8, LzError> {
+            let byte = *input.get(*pos).ok_or(LzError::UnexpectedEnd { offset })?;
+            *pos += 1;
+            Ok(byte)
+        };
+
+        let header = next(&mut pos)?;
+        if header == END {
+            return Ok((output, pos));
+        }
+
+        let (command, length) = if header >> 5 == 0b111 {
+            let low = next(&mut pos)?;
+            (
+                Command::from(header >> 2),
+                (((header & 0x03) as usize) << 8 | low as usize) + 1,
+            )
+        } else {
+            (Command::from(header >> 5), (header & 0x1F) as usize + 1)
+        };
+
+        match command {
+            Command::DirectCopy => {
+                let bytes = input
+                    .get(pos..pos + length)
+                    .ok_or(LzError::UnexpectedEnd { offset })?;
+                output.extend_from_slice(bytes);
+                pos += length;
+            }
+            Command::ByteFill => {
+                let byte = next(&mut pos)?;
+                output.resize(output.len() + length, byte);
+            }
+            Command::WordFill => {
+                let word = [next(&mut pos)?, next(&mut pos)?];
+                output.extend((0..length).map(|i| word[i % 2]));
+            }
+            Command::IncrementFill => {
+                let start = next(&mut pos)?;
+                output.extend((0..length).map(|i| start.wrapping_add(i as u8)));
+            }
+            Command::DictionaryCopy
+            | Command::DictionaryCopyInverted
+            | Command::RelativeCopy
+            | Command::RelativeCopyInverted => {
+                let source = match command {
+                    Command::DictionaryCopy | Command::DictionaryCopyInverted => {
+                        u16::from_le_bytes([next(&mut pos)?, next(&mut pos)?]) as usize
+                    }
+                    _ => {
+                        let back = next(&mut pos)? as usize;
+                        output.len().checked_sub(back).ok_or(LzError::InvalidCopy {
+                            offset,
+                            source: 0,
+                            output_len: output.len(),
+                        })?
+                    }
+                };
+                if source >= output.len() {
+                    return Err(LzError::InvalidCopy {
+                        offset,
+                        source,
+                        output_len: output.len(),
+                    });
+                }
+
+                let invert = matches!(
+                    command,
+                    Command::DictionaryCopyInverted | Command::RelativeCopyInverted
+                );
+                // the copy may overlap the bytes it writes, so go byte by byte
+                for i in 0..length {
+                    let byte = output[source + i];
+                    output.push(if invert { !byte } else { byte });
+                }
+            }
+        }
+    }
+}
+
+/// Decompresses a blob, ignoring anything after its end marker.
+pub fn decompress(input: &[u8]) -> Result<Vec<u8>, LzError> {
+    decompress_with_len(input).map(|(output, _)| output)
+}
+
+/// Longest run a single command can describe.
+pub const MAX_LENGTH: usize = 1024;
+
+// an extended `RelativeCopyInverted` header with a length above 0x300 would be
+// `0xFF`, the end marker
+const MAX_RELATIVE_INVERTED_LENGTH: usize = 0x300;
+
+// positions sharing the first bytes of a match are searched newest first, up to this many
+const MAX_CANDIDATES: usize = 256;
+
+fn header_size(command: Command, length: usize) -> usize {
+    if length <= 32 && command != Command::RelativeCopyInverted {
+        1
+    } else {
+        2
+    }
+}
+
+fn write_header(output: &mut Vec<u8>, command: Command, length: usize) {
+    let length = length - 1;
+    if header_size(command, length + 1) == 1 {
+        output.push((command as u8) << 5 | length as u8);
+    } else {
+        output.push(0xE0 | (command as u8) << 2 | (length >> 8) as u8);
+        output.push(length as u8);
+    }
+}
+
+// the best command found at one position, with its argument bytes
+struct Candidate {
+    command: Command,
+    length: usize,
+    argument: Vec<u8>,
+}
+
+impl Candidate {
+    fn size(&self) -> usize {
+        header_size(self.command, self.length) + self.argument.len()
+    }
+
+    // bytes saved compared to copying the same bytes directly
+    fn savings(&self) -> isize {
+        self.length as isize + 1 - self.size() as isize
+    }
+}
+
+fn run_length(data: &[u8], pos: usize, max: usize, expected: impl Fn(usize) -> u8) -> usize {
+    data[pos..]
+        .iter()
+        .take(max)
+        .enumerate()
+        .take_while(|(i, byte)| **byte == expected(*i))
+        .count()
+}
+
+fn fill_candidates(data: &[u8], pos: usize) -> Vec<Candidate> {
+    let first = data[pos];
+    let mut candidates = vec![
+        Candidate {
+            command: Command::ByteFill,
+            length: run_length(data, pos, MAX_LENGTH, |_| first),
+            argument: vec![first],
+        },
+        Candidate {
+            command: Command::IncrementFill,
+            length: run_length(data, pos, MAX_LENGTH, |i| first.wrapping_add(i as u8)),
+            argument: vec![first],
+        },
+    ];
+
+    if let Some(&second) = data.get(pos + 1) {
+        let word = [first, second];
+        candidates.push(Candidate {
+            command: Command::WordFill,
+            length: run_length(data, pos, MAX_LENGTH, |i| word[i % 2]),
+            argument: word.to_vec(),
+        });
+    }
+
+    candidates
+}
+
+// the longest earlier match of the data at `pos`, plain and inverted
+fn copy_candidates(data: &[u8], pos: usize, index: &[&[usize]; 2]) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+
+    for (inverted, positions) in index.iter().enumerate() {
+        let invert = if inverted == 1 { 0xFF } else { 0x00 };
+        let mut best: Option<(usize, usize)> = None;
+
+        for &source in positions.iter().rev().take(MAX_CANDIDATES) {
+            // the decompressor copies byte by byte, so the source may overlap the output
+            let length = data[pos..]
+                .iter()
+                .take(MAX_LENGTH)
+                .enumerate()
+                .take_while(|(i, byte)| data[source + i] ^ invert == **byte)
+                .count();
+            if best.is_none_or(|(_, best_length)| length > best_length) {
+                best = Some((source, length));
+            }
+        }
+
+        let Some((source, length)) = best else {
+            continue;
+        };
+
+        let back = pos - source;
+        let (command, argument) = match (inverted == 1, back <= 0xFF) {
+            (false, true) => (Command::RelativeCopy, vec![back as u8]),
+            (true, true) => (Command::RelativeCopyInverted, vec![back as u8]),
+            (false, false) => (
+                Command::DictionaryCopy,
+                (source as u16).to_le_bytes().to_vec(),
+            ),
+            (true, false) => (
+                Command::DictionaryCopyInverted,
+                (source as u16).to_le_bytes().to_vec(),
+            ),
+        };
+        let length = match command {
+            Command::RelativeCopyInverted => length.min(MAX_RELATIVE_INVERTED_LENGTH),
+            _ => length,
+        };
+        candidates.push(Candidate {
+            command,
+            length,
+            argument,
+        });
+    }
+
+    candidates
+}
+
+fn flush_direct_copy(output: &mut Vec<u8>, literal: &[u8]) {
+    for chunk in literal.chunks(MAX_LENGTH) {
+        write_header(output, Command::DirectCopy, chunk.len());
+        output.extend_from_slice(chunk);
+    }
+}
+
+/// Compresses `data` so that `decompress` returns it unchanged.
+///
+/// Every position picks the command saving the most bytes over a direct copy,
+/// which is not optimal but close to what the game's own data uses.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut literal_start = 0;
+    let mut pos = 0;
+
+    // earlier positions by their first two bytes, and by the inverse of those
+    let mut positions: HashMap<[u8; 2], Vec<usize>> = HashMap::new();
+    let mut indexed = 0;
+
+    while pos < data.len() {
+        // only positions whose address fits a dictionary copy can be sources
+        while indexed < pos && indexed + 1 < data.len() && indexed <= u16::MAX as usize {
+            positions
+                .entry([data[indexed], data[indexed + 1]])
+                .or_default()
+                .push(indexed);
+            indexed += 1;
+        }
+
+        let mut candidates = fill_candidates(data, pos);
+        if let Some(&next) = data.get(pos + 1) {
+            let index = [[data[pos], next], [!data[pos], !next]]
+                .map(|key| positions.get(&key).map_or(&[][..], Vec::as_slice));
+            candidates.extend(copy_candidates(data, pos, &index));
+        }
+
+        let best = candidates
+            .into_iter()
+            .filter(|candidate| candidate.savings() > 0)
+            .max_by_key(|candidate| (candidate.savings(), candidate.length));
+
+        match best {
+            Some(candidate) => {
+                flush_direct_copy(&mut output, &data[literal_start..pos]);
+                write_header(&mut output, candidate.command, candidate.length);
+                output.extend_from_slice(&candidate.argument);
+                pos += candidate.length;
+                literal_start = pos;
+            }
+            None => pos += 1,
+        }
+    }
+
+    flush_direct_copy(&mut output, &data[literal_start..]);
+    output.push(END);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    fn assert_round_trip(data: &[u8]) {
+        let compressed = compress(data);
+        assert_eq!(decompress(&compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn every_command_decompresses() {
+        let blob = [
+            0x02, 1, 2, 3, // direct copy
+            0x22, 9, // byte fill
+            0x43, 7, 8, // word fill
+            0x62, 0xFE, // increment fill
+            0x81, 0x00, 0x00, // dictionary copy
+            0xA1, 0x03, 0x00, // dictionary copy inverted
+            0xC1, 0x04, // relative copy
+            0xFC, 0x01, 0x02, // relative copy inverted, extended
+            0xE4, 0x02, 5, // byte fill, extended
+            END,
+        ];
+        let mut expected = vec![1, 2, 3, 9, 9, 9, 7, 8, 7, 8, 0xFE, 0xFF, 0x00];
+        expected.extend([1, 2, !9, !9]);
+        expected.extend([1, 2]);
+        expected.extend([!1, !2]);
+        expected.extend([5, 5, 5]);
+
+        assert_eq!(decompress_with_len(&blob).unwrap(), (expected, blob.len()));
+    }
+
+    #[test]
+    fn copies_may_overlap_their_output() {
+        assert_eq!(
+            decompress(&[0x01, 1, 2, 0xC5, 0x02, END]).unwrap(),
+            [1, 2, 1, 2, 1, 2, 1, 2]
+        );
+        assert_eq!(
+            decompress(&[0x00, 7, 0x82, 0x00, 0x00, END]).unwrap(),
+            [7, 7, 7, 7]
+        );
+    }
+
+    #[test]
+    fn extended_headers_reach_the_longest_run() {
+        assert_eq!(
+            decompress(&[0xE7, 0xFF, 0xAA, END]).unwrap(),
+            [0xAA; MAX_LENGTH]
+        );
+    }
+
+    #[test]
+    fn data_after_the_end_marker_is_ignored() {
+        assert_eq!(
+            decompress_with_len(&[0x20, 7, END, 1, 2, 3]).unwrap(),
+            (vec![7], 3)
+        );
+    }
+
+    #[test]
+    fn malformed_blobs_are_errors() {
+        assert_eq!(
+            decompress(&[0x03, 1, 2]),
+            Err(LzError::UnexpectedEnd { offset: 0 })
+        );
+        assert_eq!(
+            decompress(&[0x20, 1]),
+            Err(LzError::UnexpectedEnd { offset: 2 })
+        );
+        assert_eq!(
+            decompress(&[0x00, 1, 0x80, 0x05, 0x00, END]),
+            Err(LzError::InvalidCopy {
+                offset: 2,
+                source: 5,
+                output_len: 1
+            })
+        );
+        assert_eq!(
+            decompress(&[0x00, 1, 0xC0, 0x02, END]),
+            Err(LzError::InvalidCopy {
+                offset: 2,
+                source: 0,
+                output_len: 1
+            })
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        assert_round_trip(&[]);
+        assert_round_trip(&[0x42]);
+        assert_round_trip(&[0; 5000]);
+        assert_round_trip(&(0..=255).cycle().take(3000).collect::<Vec<u8>>());
+        assert_round_trip(&[0x12, 0x34].repeat(1500));
+
+        let mut rng = StdRng::seed_from_u64(21);
+        let noise: Vec<u8> = (0..4000).map(|_| rng.gen()).collect();
+        assert_round_trip(&noise);
+
+        // repeated and inverted blocks of noise, near and far apart
+        let mut blocks = noise[..300].to_vec();
+        blocks.extend(noise[..300].iter().map(|byte| !byte));
+        blocks.extend_from_slice(&noise[1000..1500]);
+        blocks.extend_from_slice(&noise[..300]);
+        blocks.extend(noise[1000..1500].iter().map(|byte| !byte));
+        assert_round_trip(&blocks);
+    }
+
+    #[test]
+    fn repetitive_data_shrinks() {
+        let mut data = vec![0; 2048];
+        data.extend([0x00, 0x80].repeat(512));
+        data.extend(0..200);
+        let compressed = compress(&data);
+        assert!(compressed.len() < 32, "{} bytes", compressed.len());
+    }
+}
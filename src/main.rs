@@ -1,59 +1,794 @@
-use std::io::Read;
-
-use log::error;
-pub mod constants;
-pub mod shapes;
-pub mod types;
-use types::Room;
-
-fn main() {
-    let my_path = std::path::Path::new("./bins");
-    if !my_path.exists() {
-        println!("Folder not found");
-        println!("{}", std::path::absolute(my_path).unwrap().display());
-        return;
-    }
-
-    // get all files in the folder
-    let entries = std::fs::read_dir(my_path).unwrap();
-    for entry in entries {
-        // error handling
-        if entry.is_err() {
-            error!("Error getting file: {:?}", entry.err());
-            continue;
-        }
-        let entry = entry.unwrap();
-        let path = entry.path();
-
-        // is file a .room file?
-        if !path.is_file() || !path.exists() || path.extension().unwrap() != "room" {
-            continue;
-        }
-
-        // Get room id before .room extension
-        let room_id = path
-            .file_stem()
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .split("_Room_")
-            .last()
-            .unwrap();
-        println!("Room ID: {}", room_id);
-
-        // open file
-        let file = std::fs::File::open(path.clone());
-        if file.is_err() {
-            error!("Error opening file: {:?}", file.err());
-            continue;
-        }
-
-        let mut file = file.unwrap();
-        let mut bytes = Vec::new();
-        file.read_to_end(&mut bytes).unwrap();
-
-        let mut room = Room::from_bytes(&bytes);
-        room.room_id = room_id.to_string();
-        room.save_image();
+use std::{
+    collections::HashSet,
+    fmt::{self, Write},
+    io::Read,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Mutex,
+};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use image::Rgba;
+use mamamia::{ldtk, svg, tiled, world, AreaIndex, PlmCategory, RenderStyle, Rom, Room, Scale};
+use rayon::prelude::*;
+
+#[derive(Parser)]
+#[command(version, about = "Render and inspect Super Metroid .room files")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(flatten)]
+    Rooms(RoomCommand),
+    /// Print the colours used for rendering as a theme file
+    Theme {
+        #[command(flatten)]
+        style: StyleArgs,
+    },
+}
+
+// the commands reading rooms
+#[derive(Subcommand)]
+enum RoomCommand {
+    /// Render the collision of every room to an image or map
+    Render {
+        #[command(flatten)]
+        input: InputArgs,
+
+        /// Directory the images are written to
+        #[arg(short, long, default_value = "./output")]
+        output: PathBuf,
+
+        /// File name template without extension, supports {room_id}, {area}, {area_index} and {room_index}
+        #[arg(short, long, default_value = "{room_id}")]
+        name: String,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = Format::Png)]
+        format: Format,
+
+        #[command(flatten)]
+        style: StyleArgs,
+
+        #[command(flatten)]
+        scale: ScaleArgs,
+    },
+    /// Export every room as a level of a single LDtk project
+    Ldtk {
+        #[command(flatten)]
+        input: InputArgs,
+
+        /// Path of the project file
+        #[arg(short, long, default_value = "./output/rooms.ldtk")]
+        output: PathBuf,
+    },
+    /// Render one map per area with every room at its map position
+    World {
+        #[command(flatten)]
+        input: InputArgs,
+
+        /// Directory the maps are written to
+        #[arg(short, long, default_value = "./output")]
+        output: PathBuf,
+
+        /// File name template without extension, supports {area} and {area_index}
+        #[arg(short, long, default_value = "{area}")]
+        name: String,
+
+        #[command(flatten)]
+        style: StyleArgs,
+
+        #[command(flatten)]
+        scale: ScaleArgs,
+    },
+    /// Print the header fields of every room
+    Info {
+        #[command(flatten)]
+        input: InputArgs,
+    },
+    /// Print the block type and BTS grid of every room, or the whole parsed room
+    Dump {
+        #[command(flatten)]
+        input: InputArgs,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = DumpFormat::Text)]
+        format: DumpFormat,
+
+        /// Write one file per room to this directory instead of printing
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// File name template without extension, supports {room_id}, {area}, {area_index} and {room_index}
+        #[arg(short, long, default_value = "{room_id}")]
+        name: String,
+    },
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum Format {
+    /// Raster image of the collision
+    Png,
+    /// Vector image with one path per polygon, in fixed colours
+    Svg,
+    /// Tiled map, with the tilesets written next to it, in fixed colours
+    Tmx,
+}
+
+impl Format {
+    fn extension(&self) -> &'static str {
+        match self {
+            Format::Png => "png",
+            Format::Svg => "svg",
+            Format::Tmx => "tmx",
+        }
+    }
+}
+
+#[derive(Args)]
+struct StyleArgs {
+    /// Theme file overriding the default colours of png images and world maps, see `theme` for the format
+    #[arg(long)]
+    theme: Option<PathBuf>,
+
+    /// Start from random colours generated from this seed instead of the default theme
+    #[arg(long, value_name = "SEED")]
+    debug_random: Option<u64>,
+
+    /// Draw every continuous slope as a single line
+    #[arg(long)]
+    slope_runs: bool,
+
+    /// Draw the layer 2 background faded under the collision
+    #[arg(long)]
+    layer2: bool,
+
+    /// Draw the PLMs of these categories over the collision: item, door, gate, scroll or other (--rom only)
+    #[arg(long, value_name = "CATEGORY", value_delimiter = ',')]
+    plms: Vec<PlmCategory>,
+
+    /// Label the drawn PLMs with their item name or id
+    #[arg(long)]
+    plm_labels: bool,
+}
+
+#[derive(Args)]
+struct ScaleArgs {
+    /// Draw every pixel as a square of this many pixels
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=8))]
+    scale: Option<u32>,
+
+    /// Render a thumbnail where every pixel covers this many tiles, can be fractional
+    #[arg(long, value_name = "TILES_PER_PIXEL", conflicts_with = "scale", value_parser = parse_tiles_per_pixel)]
+    thumbnail: Option<f32>,
+}
+
+fn parse_tiles_per_pixel(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(tiles) if tiles > 0.0 && tiles.is_finite() => Ok(tiles),
+        _ => Err(format!("expected a positive number, got `{}`", value)),
+    }
+}
+
+impl ScaleArgs {
+    fn scale(&self) -> Scale {
+        match (self.scale, self.thumbnail) {
+            (_, Some(tiles)) => Scale::TilesPerPixel(tiles),
+            (Some(factor), None) => Scale::Pixels(factor),
+            (None, None) => Scale::default(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum DumpFormat {
+    /// `T:BB` grid of block types and BTS
+    Text,
+    /// Every parsed field as JSON
+    #[cfg(feature = "serde")]
+    Json,
+    /// Every parsed field as RON
+    #[cfg(feature = "serde")]
+    Ron,
+}
+
+impl DumpFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            DumpFormat::Text => "txt",
+            #[cfg(feature = "serde")]
+            DumpFormat::Json => "json",
+            #[cfg(feature = "serde")]
+            DumpFormat::Ron => "ron",
+        }
+    }
+}
+
+#[derive(Args)]
+struct InputArgs {
+    /// .room files, directories containing .room files or glob patterns
+    #[arg(default_value = "./bins")]
+    inputs: Vec<String>,
+
+    /// Read every room from a Super Metroid ROM instead of .room files
+    #[arg(long, conflicts_with = "inputs")]
+    rom: Option<PathBuf>,
+
+    /// Only process rooms of this area, by name or index (can be repeated)
+    #[arg(short, long = "area")]
+    areas: Vec<AreaIndex>,
+
+    /// Number of rooms processed in parallel, 0 uses every core
+    #[arg(short, long, default_value_t = 0)]
+    jobs: usize,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let command = match &cli.command {
+        Command::Theme { style } => return print_theme(style),
+        Command::Rooms(command) => command,
+    };
+    let input = match command {
+        RoomCommand::Render { input, .. }
+        | RoomCommand::Ldtk { input, .. }
+        | RoomCommand::World { input, .. }
+        | RoomCommand::Info { input }
+        | RoomCommand::Dump { input, .. } => input,
+    };
+
+    let rom = match &input.rom {
+        Some(path) => match Rom::open(path) {
+            Ok(rom) => Some(rom),
+            Err(e) => {
+                eprintln!("Error reading {}: {}", path.display(), e);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => None,
+    };
+
+    let sources: Vec<RoomSource> = match &rom {
+        Some(rom) => rom
+            .room_addresses()
+            .into_iter()
+            .map(|address| RoomSource::Rom(rom, address))
+            .collect(),
+        None => collect_room_paths(&input.inputs)
+            .into_iter()
+            .map(RoomSource::File)
+            .collect(),
+    };
+    if sources.is_empty() {
+        match &input.rom {
+            Some(path) => eprintln!("No rooms found in {}", path.display()),
+            None => eprintln!("No .room files found in {}", input.inputs.join(", ")),
+        }
+        return ExitCode::FAILURE;
+    }
+
+    // svg and tmx use fixed colours, only png images and world maps are styled
+    if let RoomCommand::Render { format, style, .. } = command {
+        if !matches!(format, Format::Png) && (style.theme.is_some() || style.debug_random.is_some())
+        {
+            eprintln!("Warning: --theme and --debug-random only apply to png images");
+        }
+    }
+
+    let style = match command {
+        RoomCommand::Render { style, .. } | RoomCommand::World { style, .. } => load_style(style),
+        _ => Ok(RenderStyle::default()),
+    };
+    let style = match style {
+        Ok(style) => style,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // 0 lets rayon use one thread per core
+    let pool = match rayon::ThreadPoolBuilder::new()
+        .num_threads(input.jobs)
+        .build()
+    {
+        Ok(pool) => pool,
+        Err(e) => {
+            eprintln!("Error starting {} jobs: {}", input.jobs, e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // results are collected in source order, so the output doesn't depend on the scheduling
+    let tileset_dirs = Mutex::new(HashSet::new());
+    let results: Vec<Result<Processed, String>> = pool.install(|| {
+        sources
+            .par_iter()
+            .map(|source| process_room(command, input, &style, source, &tileset_dirs))
+            .collect()
+    });
+
+    let mut failures = Vec::new();
+    let mut skipped = 0;
+    let mut collected_rooms = Vec::new();
+    for (source, result) in sources.iter().zip(results) {
+        match result {
+            Ok(Processed::Skipped) => skipped += 1,
+            Ok(Processed::Output(output)) => print!("{}", output),
+            Ok(Processed::Collected(room)) => collected_rooms.push(*room),
+            Err(e) => failures.push((source, e)),
+        }
+    }
+
+    let mut failed = !failures.is_empty();
+    match command {
+        RoomCommand::Ldtk { output, .. } => {
+            println!("{} rooms -> {}", collected_rooms.len(), output.display());
+            if let Err(e) = ldtk::save_ldtk(&collected_rooms, output) {
+                eprintln!("Error saving {}: {}", output.display(), e);
+                failed = true;
+            }
+        }
+        RoomCommand::World {
+            output,
+            name,
+            scale,
+            ..
+        } => {
+            // one area at a time, a full size world map takes a lot of memory
+            for area in world::areas(&collected_rooms) {
+                let Some(map) = world::render_area(&collected_rooms, area, &style, scale.scale())
+                else {
+                    continue;
+                };
+                let map_name = name
+                    .replace("{area}", &map.area.to_string())
+                    .replace("{area_index}", &map.area.index().to_string());
+                let map_path = output.join(map_name).with_extension("png");
+                println!("Area: {} -> {}", map.area, map_path.display());
+                if let Err(e) = map.save(&map_path) {
+                    eprintln!("Error saving {}: {}", map_path.display(), e);
+                    failed = true;
+                }
+            }
+        }
+        _ => {}
+    }
+
+    // the summary goes to stderr to keep dumps on stdout clean
+    eprintln!(
+        "{} rooms: {} ok, {} skipped, {} failed",
+        sources.len(),
+        sources.len() - skipped - failures.len(),
+        skipped,
+        failures.len()
+    );
+    for (source, e) in &failures {
+        eprintln!("  {}: {}", source, e);
+    }
+
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+// where a room is read from
+enum RoomSource<'a> {
+    File(PathBuf),
+    /// The `--rom` image and the address of the room header in it.
+    Rom(&'a Rom, u32),
+}
+
+impl fmt::Display for RoomSource<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoomSource::File(path) => write!(f, "{}", path.display()),
+            RoomSource::Rom(_, address) => write!(f, "${:06X}", address),
+        }
+    }
+}
+
+// what happened to a single room
+enum Processed {
+    /// Filtered out by `--area`.
+    Skipped,
+    /// Done, with the text to print.
+    Output(String),
+    /// Kept for a command working on all rooms at once.
+    Collected(Box<Room>),
+}
+
+fn process_room(
+    command: &RoomCommand,
+    input: &InputArgs,
+    style: &RenderStyle,
+    source: &RoomSource,
+    tileset_dirs: &Mutex<HashSet<PathBuf>>,
+) -> Result<Processed, String> {
+    let room = match source {
+        RoomSource::File(path) => load_room(path)?,
+        RoomSource::Rom(rom, address) => rom.room(*address).map_err(|e| e.to_string())?,
+    };
+
+    if !input.areas.is_empty() && !input.areas.contains(&room.area_index()) {
+        return Ok(Processed::Skipped);
+    }
+
+    match command {
+        RoomCommand::Render {
+            output,
+            name,
+            format,
+            scale,
+            ..
+        } => {
+            let image_path = output
+                .join(format_name(name, &room))
+                .with_extension(format.extension());
+
+            match format {
+                Format::Png => room
+                    .save_image_scaled(&image_path, style, scale.scale())
+                    .map_err(|e| e.to_string()),
+                Format::Svg => svg::save_svg(&room, &image_path).map_err(|e| e.to_string()),
+                Format::Tmx => {
+                    // the maps of a directory share one copy of the tilesets, the lock is
+                    // held while saving so a failed save is retried by the next room
+                    let dir = image_path.parent().unwrap_or(Path::new("")).to_path_buf();
+                    {
+                        let mut saved = tileset_dirs.lock().unwrap();
+                        if !saved.contains(&dir) {
+                            tiled::save_tilesets(&dir).map_err(|e| {
+                                format!("saving tilesets to {}: {}", dir.display(), e)
+                            })?;
+                            saved.insert(dir);
+                        }
+                    }
+                    tiled::save_tmx(&room, &image_path).map_err(|e| e.to_string())
+                }
+            }
+            .map_err(|e| format!("saving {}: {}", image_path.display(), e))?;
+
+            Ok(Processed::Output(format!(
+                "Room ID: {} -> {}\n",
+                room.room_id,
+                image_path.display()
+            )))
+        }
+        RoomCommand::Ldtk { .. } | RoomCommand::World { .. } => {
+            Ok(Processed::Collected(Box::new(room)))
+        }
+        RoomCommand::Info { .. } => Ok(Processed::Output(info_text(&room))),
+        RoomCommand::Dump {
+            format,
+            output,
+            name,
+            ..
+        } => {
+            let dump = dump_room(&room, *format)?;
+            let Some(output) = output else {
+                return Ok(Processed::Output(dump));
+            };
+
+            let dump_path = output
+                .join(format_name(name, &room))
+                .with_extension(format.extension());
+            std::fs::create_dir_all(output)
+                .and_then(|_| std::fs::write(&dump_path, dump))
+                .map_err(|e| format!("saving {}: {}", dump_path.display(), e))?;
+
+            Ok(Processed::Output(format!(
+                "Room ID: {} -> {}\n",
+                room.room_id,
+                dump_path.display()
+            )))
+        }
+    }
+}
+
+fn load_style(args: &StyleArgs) -> Result<RenderStyle, String> {
+    let mut style = match args.debug_random {
+        Some(seed) => RenderStyle::debug_random(seed),
+        None => RenderStyle::default(),
+    };
+
+    if let Some(path) = &args.theme {
+        let theme = std::fs::read_to_string(path)
+            .map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+        style
+            .apply_theme(&theme)
+            .map_err(|e| format!("Error in {}: {}", path.display(), e))?;
+    }
+
+    if args.slope_runs && style.slope_runs.is_none() {
+        style.slope_runs = Some(Rgba([255, 255, 255, 255]));
+    }
+    if args.layer2 && style.layer2.is_none() {
+        // half transparent, so the background stays faded under the collision
+        style.layer2 = Some(Rgba([64, 64, 80, 128]));
+    }
+    for category in &args.plms {
+        let color = match category {
+            PlmCategory::Item => Rgba([0, 255, 255, 255]),
+            PlmCategory::Door => Rgba([255, 128, 0, 255]),
+            PlmCategory::Gate => Rgba([255, 64, 64, 255]),
+            PlmCategory::Scroll => Rgba([128, 128, 255, 255]),
+            PlmCategory::Other => Rgba([255, 255, 255, 255]),
+        };
+        style.plms.entry(*category).or_insert(color);
+    }
+    style.plm_labels |= args.plm_labels;
+
+    Ok(style)
+}
+
+fn print_theme(args: &StyleArgs) -> ExitCode {
+    match load_style(args) {
+        Ok(style) => {
+            print!("{}", style.to_theme());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// expand files, directories and glob patterns into a sorted list of .room files
+fn collect_room_paths(inputs: &[String]) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+
+    for input in inputs {
+        let path = Path::new(input);
+        if path.is_dir() {
+            match std::fs::read_dir(path) {
+                Ok(entries) => paths.extend(
+                    entries
+                        .filter_map(|entry| entry.ok())
+                        .map(|entry| entry.path())
+                        .filter(|path| is_room_file(path)),
+                ),
+                Err(e) => eprintln!("Error reading {}: {}", path.display(), e),
+            }
+        } else if path.is_file() {
+            paths.push(path.to_path_buf());
+        } else {
+            match glob::glob(input) {
+                Ok(entries) => paths.extend(
+                    entries
+                        .filter_map(|entry| entry.ok())
+                        .filter(|path| is_room_file(path)),
+                ),
+                Err(e) => eprintln!("Invalid pattern {}: {}", input, e),
+            }
+        }
+    }
+
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+fn is_room_file(path: &Path) -> bool {
+    path.is_file() && path.extension().is_some_and(|ext| ext == "room")
+}
+
+fn load_room(path: &Path) -> Result<Room, String> {
+    let mut file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+
+    let mut room = Room::try_from_bytes(&bytes).map_err(|e| e.to_string())?;
+    room.room_id = room_id_from_path(path);
+    Ok(room)
+}
+
+// get room id before .room extension
+fn room_id_from_path(path: &Path) -> String {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+
+    stem.split("_Room_").last().unwrap_or_default().to_string()
+}
+
+fn format_name(template: &str, room: &Room) -> String {
+    template
+        .replace("{room_id}", &room.room_id)
+        .replace("{area}", &room.area_index().to_string())
+        .replace("{area_index}", &room.area_index().index().to_string())
+        .replace("{room_index}", &format!("{:02X}", room.room_index()))
+}
+
+fn info_text(room: &Room) -> String {
+    let mut info = String::new();
+    writeln!(info, "Room ID: {}", room.room_id).unwrap();
+    let area = room.area_index();
+    writeln!(info, "  area:          {} ({})", area, area.index()).unwrap();
+    writeln!(info, "  room index:    {:#04X}", room.room_index()).unwrap();
+    writeln!(info, "  map position:  {}, {}", room.map_x(), room.map_y()).unwrap();
+    writeln!(
+        info,
+        "  size:          {}x{} screens, {}x{} tiles",
+        room.room_width(),
+        room.room_height(),
+        room.get_room_width_tiles(),
+        room.get_room_height_tiles()
+    )
+    .unwrap();
+    writeln!(
+        info,
+        "  scroll:        up {:#04X}, down {:#04X}",
+        room.up_scroll(),
+        room.down_scroll()
+    )
+    .unwrap();
+    writeln!(
+        info,
+        "  special gfx:   {:#010b}",
+        room.special_graphics_bitflag()
+    )
+    .unwrap();
+    writeln!(info, "  door out:      {:#04X}", room.door_out_pointer()).unwrap();
+    writeln!(
+        info,
+        "  unknown:       {:02X?}",
+        room.unknown_header_bytes()
+    )
+    .unwrap();
+    writeln!(
+        info,
+        "  layer 2:       {}",
+        if room.has_layer2() { "yes" } else { "no" }
+    )
+    .unwrap();
+    writeln!(info, "  contours:      {}", room.collision_contours().len()).unwrap();
+
+    // only rooms read from a rom know their doors and states
+    if !room.doors.is_empty() {
+        writeln!(info, "  doors:         {}", room.doors.len()).unwrap();
+    }
+    if !room.states.is_empty() {
+        writeln!(info, "  states:        {}", room.states.len()).unwrap();
+    }
+    for state in &room.states {
+        writeln!(
+            info,
+            "    ${:06X} {}: {} plms, {} enemies, {} fx",
+            state.address,
+            state.condition,
+            state.plms.len(),
+            state.enemies.len(),
+            state.fx.len()
+        )
+        .unwrap();
+    }
+    info
+}
+
+fn dump_room(room: &Room, format: DumpFormat) -> Result<String, String> {
+    match format {
+        DumpFormat::Text => Ok(dump_text(room)),
+        #[cfg(feature = "serde")]
+        DumpFormat::Json => serde_json::to_string_pretty(room)
+            .map(|json| json + "\n")
+            .map_err(|e| e.to_string()),
+        #[cfg(feature = "serde")]
+        DumpFormat::Ron => ron::ser::to_string_pretty(room, ron::ser::PrettyConfig::default())
+            .map(|ron| ron + "\n")
+            .map_err(|e| e.to_string()),
+    }
+}
+
+// one `T:BB` entry per cell, block type nibble and bts byte in hex
+fn dump_text(room: &Room) -> String {
+    let mut dump = format!("Room ID: {}\n", room.room_id);
+    let room_width = room.get_room_width_tiles() as usize;
+    for row in room.cells.chunks(room_width) {
+        let line: Vec<String> = row
+            .iter()
+            .map(|cell| format!("{:X}:{:02X}", cell.block_type as u8, cell.bts()))
+            .collect();
+        dump.push_str(&line.join(" "));
+        dump.push('\n');
+    }
+    dump
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // an empty single screen room
+    fn room_at(area: u8, room_index: u8) -> Room {
+        let mut bytes = vec![area, room_index, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        bytes.resize(bytes.len() + 256 * 3, 0);
+        Room::try_from_bytes(&bytes).unwrap()
+    }
+
+    #[test]
+    fn cli_is_valid() {
+        use clap::CommandFactory;
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn render_arguments_are_parsed() {
+        let cli = Cli::try_parse_from([
+            "mamamia",
+            "render",
+            "rooms",
+            "-o",
+            "out",
+            "-n",
+            "{area}_{room_index}",
+            "-a",
+            "maridia",
+            "-a",
+            "1",
+            "--scale",
+            "2",
+        ])
+        .unwrap();
+        let Command::Rooms(RoomCommand::Render {
+            input,
+            output,
+            name,
+            scale,
+            ..
+        }) = cli.command
+        else {
+            panic!("expected the render command");
+        };
+        assert_eq!(input.inputs, ["rooms"]);
+        assert_eq!(input.areas, [AreaIndex::Maridia, AreaIndex::Brinstar]);
+        assert_eq!(output, PathBuf::from("out"));
+        assert_eq!(name, "{area}_{room_index}");
+        assert_eq!(scale.scale(), Scale::Pixels(2));
+    }
+
+    #[test]
+    fn layer2_is_faded_by_default() {
+        let cli = Cli::try_parse_from(["mamamia", "render", "--layer2"]).unwrap();
+        let Command::Rooms(RoomCommand::Render { style, .. }) = cli.command else {
+            panic!("expected the render command");
+        };
+        let color = load_style(&style).unwrap().layer2.unwrap();
+        assert!(color[3] < 255);
+    }
+
+    #[test]
+    fn invalid_arguments_are_rejected() {
+        for args in [
+            &["mamamia", "render", "--area", "zebes"][..],
+            &["mamamia", "render", "--scale", "9"],
+            &["mamamia", "render", "--scale", "2", "--thumbnail", "1"],
+            &["mamamia", "render", "--thumbnail", "0"],
+            &["mamamia", "info", "--rom", "game.sfc", "rooms"],
+        ] {
+            assert!(Cli::try_parse_from(args).is_err(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn name_template_is_filled_in() {
+        let mut room = room_at(4, 0x1A);
+        room.room_id = "Maridia_1A".to_string();
+        assert_eq!(
+            format_name("{room_id}-{area}-{area_index}-{room_index}", &room),
+            "Maridia_1A-maridia-4-1A"
+        );
+        assert_eq!(
+            format_name("{area}_{room_index}", &room_at(3, 5)),
+            "wrecked_ship_05"
+        );
     }
 }
@@ -0,0 +1,776 @@
+use std::{collections::HashMap, fmt::Write, path::Path};
+
+use image::{imageops, Rgba, RgbaImage};
+use imageproc::{
+    drawing::{
+        draw_filled_circle_mut, draw_filled_rect_mut, draw_hollow_rect_mut, draw_line_segment_mut,
+    },
+    rect::Rect,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    constants::CELL_SIZE,
+    error::ThemeError,
+    state::{Plm, PlmCategory},
+    types::{BlockType, SlopeType, TreatAsSlopeType},
+};
+
+/// How the inside of a cell is drawn.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Pattern {
+    /// Not drawn at all.
+    None,
+    /// The whole cell is filled.
+    Fill,
+    /// Diagonal lines going up to the right, with an outline.
+    Hatch,
+    /// Diagonal lines in both directions, with an outline.
+    CrossHatch,
+    /// Horizontal lines, with an outline.
+    HorizontalLines,
+    /// Only the border of the cell.
+    Outline,
+    /// An X from corner to corner, with an outline.
+    Cross,
+}
+
+impl Pattern {
+    pub const ALL: [Pattern; 7] = [
+        Pattern::None,
+        Pattern::Fill,
+        Pattern::Hatch,
+        Pattern::CrossHatch,
+        Pattern::HorizontalLines,
+        Pattern::Outline,
+        Pattern::Cross,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pattern::None => "none",
+            Pattern::Fill => "fill",
+            Pattern::Hatch => "hatch",
+            Pattern::CrossHatch => "cross_hatch",
+            Pattern::HorizontalLines => "horizontal_lines",
+            Pattern::Outline => "outline",
+            Pattern::Cross => "cross",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct BlockStyle {
+    pub color: Rgba<u8>,
+    pub pattern: Pattern,
+}
+
+impl BlockStyle {
+    pub const fn new(color: Rgba<u8>, pattern: Pattern) -> Self {
+        BlockStyle { color, pattern }
+    }
+}
+
+/// The palette used by `Room::render_image_with_style`.
+///
+/// Only the raster images and world maps are styled, the SVG, Tiled and LDtk
+/// exports use their own fixed colours.
+///
+/// `BlockType::Solid` and `BlockType::Slope` are drawn by the collision pass
+/// with the `collision` colours and ignore their `blocks` entry, copy blocks
+/// use the style of the block they resolve to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderStyle {
+    pub blocks: HashMap<BlockType, BlockStyle>,
+    /// Colour of solid and slope cells, by how the cell is treated.
+    pub collision: HashMap<TreatAsSlopeType, Rgba<u8>>,
+    /// Colour of slope cells of a given type, overriding `collision`.
+    pub slopes: HashMap<SlopeType, Rgba<u8>>,
+    /// Colour of the border around the room.
+    pub outline: Rgba<u8>,
+    /// Colour of the line drawn over every `SlopeRun`, not drawn when `None`.
+    pub slope_runs: Option<Rgba<u8>>,
+    /// Colour of the layer 2 background under the collision, not drawn when
+    /// `None`. Its alpha is kept, so a partial alpha fades the background.
+    pub layer2: Option<Rgba<u8>>,
+    /// Colour of the PLMs of each category in the overlay, categories without
+    /// a colour are not drawn.
+    pub plms: HashMap<PlmCategory, Rgba<u8>>,
+    /// Whether the drawn PLMs are labelled with their item name or id.
+    pub plm_labels: bool,
+}
+
+const SOLID_COLOR: Rgba<u8> = Rgba([0, 255, 0, 255]);
+
+impl RenderStyle {
+    pub fn block(&self, block_type: BlockType) -> Option<&BlockStyle> {
+        self.blocks.get(&block_type)
+    }
+
+    pub fn set_block(&mut self, block_type: BlockType, style: BlockStyle) {
+        self.blocks.insert(block_type, style);
+    }
+
+    /// Colour of a solid or slope cell, `slope_type` is `None` for solid cells.
+    pub fn collision_color(
+        &self,
+        treat_as_slope: TreatAsSlopeType,
+        slope_type: Option<SlopeType>,
+    ) -> Rgba<u8> {
+        slope_type
+            .and_then(|slope_type| self.slopes.get(&slope_type))
+            .or_else(|| self.collision.get(&treat_as_slope))
+            .copied()
+            .unwrap_or(SOLID_COLOR)
+    }
+
+    /// The default style with every colour replaced by a random one.
+    ///
+    /// Every slope type gets its own colour. The same seed always gives the
+    /// same colours, so renders stay reproducible.
+    pub fn debug_random(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut style = RenderStyle::default();
+
+        for block_type in BlockType::ALL {
+            if matches!(
+                block_type,
+                BlockType::Air | BlockType::Solid | BlockType::Slope
+            ) {
+                continue;
+            }
+
+            let pattern = style
+                .block(block_type)
+                .map_or(Pattern::Fill, |block_style| block_style.pattern);
+            style.set_block(block_type, BlockStyle::new(random_color(&mut rng), pattern));
+        }
+        for treat_as_slope in TreatAsSlopeType::ALL {
+            style
+                .collision
+                .insert(treat_as_slope, random_color(&mut rng));
+        }
+        for slope_type in SlopeType::ALL {
+            style.slopes.insert(slope_type, random_color(&mut rng));
+        }
+
+        style
+    }
+
+    /// Reads a theme file and applies it on top of the default style.
+    pub fn load_theme<P: AsRef<Path>>(path: P) -> Result<Self, ThemeError> {
+        let theme = std::fs::read_to_string(path)?;
+        let mut style = RenderStyle::default();
+        style.apply_theme(&theme)?;
+        Ok(style)
+    }
+
+    /// Overrides the entries of this style listed in `theme`.
+    ///
+    /// A theme has one `key = value` entry per line, empty lines and lines
+    /// starting with `#` are ignored. Colours are written as `#rrggbb` or
+    /// `#rrggbbaa`, names are the snake case names of the types:
+    ///
+    /// ```text
+    /// outline = #00ff00
+    /// slope_runs = none
+    /// layer2 = #40405080
+    /// plm_labels = true
+    /// plm.item = #00ffff
+    /// collision.slope_left = #ffff00
+    /// slope.slope_45 = #00ffff
+    /// block.spike = #ff0000 cross_hatch
+    /// block.air_xray = none
+    /// ```
+    pub fn apply_theme(&mut self, theme: &str) -> Result<(), ThemeError> {
+        for (i, line) in theme.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            self.apply_theme_line(line)
+                .map_err(|message| ThemeError::InvalidLine {
+                    line: i + 1,
+                    message,
+                })?;
+        }
+
+        Ok(())
+    }
+
+    fn apply_theme_line(&mut self, line: &str) -> Result<(), String> {
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("expected `key = value`, got `{}`", line))?;
+        let (key, value) = (key.trim(), value.trim());
+        let (group, name) = key.split_once('.').unwrap_or((key, ""));
+
+        match group {
+            "outline" => self.outline = parse_color(value)?,
+            "slope_runs" => {
+                self.slope_runs = match value {
+                    "none" => None,
+                    _ => Some(parse_color(value)?),
+                }
+            }
+            "layer2" => {
+                self.layer2 = match value {
+                    "none" => None,
+                    _ => Some(parse_color(value)?),
+                }
+            }
+            "plm_labels" => {
+                self.plm_labels = value
+                    .parse()
+                    .map_err(|_| format!("expected `true` or `false`, got `{}`", value))?
+            }
+            "plm" => {
+                let category = find_by_name(&PlmCategory::ALL, name, |c| c.name())?;
+                match value {
+                    "none" => self.plms.remove(&category),
+                    _ => self.plms.insert(category, parse_color(value)?),
+                };
+            }
+            "collision" => {
+                let treat_as_slope = find_by_name(&TreatAsSlopeType::ALL, name, |t| t.name())?;
+                self.collision.insert(treat_as_slope, parse_color(value)?);
+            }
+            "slope" => {
+                let slope_type = find_by_name(&SlopeType::ALL, name, |t| t.name())?;
+                match value {
+                    "none" => self.slopes.remove(&slope_type),
+                    _ => self.slopes.insert(slope_type, parse_color(value)?),
+                };
+            }
+            "block" => {
+                let block_type = find_by_name(&BlockType::ALL, name, |t| t.name())?;
+                if value == "none" {
+                    self.blocks.remove(&block_type);
+                    return Ok(());
+                }
+
+                let mut parts = value.split_whitespace();
+                let color = parse_color(parts.next().unwrap_or_default())?;
+                let pattern = match parts.next() {
+                    Some(pattern) => find_by_name(&Pattern::ALL, pattern, |p| p.name())?,
+                    None => self
+                        .block(block_type)
+                        .map_or(Pattern::Fill, |block_style| block_style.pattern),
+                };
+                self.set_block(block_type, BlockStyle::new(color, pattern));
+            }
+            _ => return Err(format!("unknown key `{}`", key)),
+        }
+
+        Ok(())
+    }
+
+    /// Writes this style as a theme, applying it to the default style gives this style back.
+    pub fn to_theme(&self) -> String {
+        let mut theme = String::new();
+        writeln!(theme, "outline = {}", color_name(self.outline)).unwrap();
+        match self.slope_runs {
+            Some(color) => writeln!(theme, "slope_runs = {}", color_name(color)).unwrap(),
+            None => writeln!(theme, "slope_runs = none").unwrap(),
+        }
+        match self.layer2 {
+            Some(color) => writeln!(theme, "layer2 = {}", color_name(color)).unwrap(),
+            None => writeln!(theme, "layer2 = none").unwrap(),
+        }
+        writeln!(theme, "plm_labels = {}", self.plm_labels).unwrap();
+        for category in PlmCategory::ALL {
+            match self.plms.get(&category) {
+                Some(color) => {
+                    writeln!(theme, "plm.{} = {}", category.name(), color_name(*color)).unwrap()
+                }
+                None => writeln!(theme, "plm.{} = none", category.name()).unwrap(),
+            }
+        }
+
+        theme.push('\n');
+        for treat_as_slope in TreatAsSlopeType::ALL {
+            let color = self.collision_color(treat_as_slope, None);
+            writeln!(
+                theme,
+                "collision.{} = {}",
+                treat_as_slope.name(),
+                color_name(color)
+            )
+            .unwrap();
+        }
+
+        if !self.slopes.is_empty() {
+            theme.push('\n');
+        }
+        for slope_type in SlopeType::ALL {
+            if let Some(color) = self.slopes.get(&slope_type) {
+                writeln!(
+                    theme,
+                    "slope.{} = {}",
+                    slope_type.name(),
+                    color_name(*color)
+                )
+                .unwrap();
+            }
+        }
+
+        theme.push('\n');
+        for block_type in BlockType::ALL {
+            match self.block(block_type) {
+                Some(block_style) => writeln!(
+                    theme,
+                    "block.{} = {} {}",
+                    block_type.name(),
+                    color_name(block_style.color),
+                    block_style.pattern.name()
+                )
+                .unwrap(),
+                None => writeln!(theme, "block.{} = none", block_type.name()).unwrap(),
+            }
+        }
+
+        theme
+    }
+}
+
+impl Default for RenderStyle {
+    fn default() -> Self {
+        let blocks = HashMap::from([
+            (
+                BlockType::AirXray,
+                BlockStyle::new(Rgba([160, 160, 160, 255]), Pattern::Outline),
+            ),
+            (
+                BlockType::Treadmill,
+                BlockStyle::new(Rgba([0, 200, 200, 255]), Pattern::HorizontalLines),
+            ),
+            (
+                BlockType::AirShot,
+                BlockStyle::new(Rgba([255, 220, 120, 255]), Pattern::Outline),
+            ),
+            (
+                BlockType::AirBomb,
+                BlockStyle::new(Rgba([255, 255, 255, 255]), Pattern::Outline),
+            ),
+            (
+                BlockType::Door,
+                BlockStyle::new(Rgba([0, 128, 255, 255]), Pattern::Fill),
+            ),
+            (
+                BlockType::Spike,
+                BlockStyle::new(Rgba([255, 0, 0, 255]), Pattern::CrossHatch),
+            ),
+            (
+                BlockType::Crumble,
+                BlockStyle::new(Rgba([255, 128, 0, 255]), Pattern::Hatch),
+            ),
+            (
+                BlockType::Shot,
+                BlockStyle::new(Rgba([255, 220, 0, 255]), Pattern::Hatch),
+            ),
+            (
+                BlockType::Grapple,
+                BlockStyle::new(Rgba([160, 0, 255, 255]), Pattern::CrossHatch),
+            ),
+            (
+                BlockType::Bomb,
+                BlockStyle::new(Rgba([255, 255, 255, 255]), Pattern::Cross),
+            ),
+        ]);
+
+        let collision = HashMap::from([
+            (TreatAsSlopeType::Solid, SOLID_COLOR),
+            (TreatAsSlopeType::SlopeLeft, Rgba([255, 255, 0, 255])),
+            (TreatAsSlopeType::SlopeRight, Rgba([255, 0, 255, 255])),
+            (TreatAsSlopeType::SlopeProtectNegX, Rgba([255, 255, 0, 255])),
+            (TreatAsSlopeType::SlopeProtectPosX, Rgba([255, 0, 255, 255])),
+        ]);
+
+        RenderStyle {
+            blocks,
+            collision,
+            slopes: HashMap::new(),
+            outline: SOLID_COLOR,
+            slope_runs: None,
+            layer2: None,
+            plms: HashMap::new(),
+            plm_labels: false,
+        }
+    }
+}
+
+fn random_color(rng: &mut impl Rng) -> Rgba<u8> {
+    Rgba([rng.gen(), rng.gen(), rng.gen(), 255])
+}
+
+fn parse_color(value: &str) -> Result<Rgba<u8>, String> {
+    let hex = value
+        .strip_prefix('#')
+        .filter(|hex| hex.len() == 6 || hex.len() == 8)
+        .ok_or_else(|| format!("expected a `#rrggbb` colour, got `{}`", value))?;
+
+    let mut color = Rgba([0, 0, 0, 255]);
+    for (i, channel) in color.0.iter_mut().enumerate().take(hex.len() / 2) {
+        *channel = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+            .map_err(|_| format!("invalid colour `{}`", value))?;
+    }
+    Ok(color)
+}
+
+fn color_name(color: Rgba<u8>) -> String {
+    let [r, g, b, a] = color.0;
+    if a == 255 {
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    } else {
+        format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+    }
+}
+
+fn find_by_name<T: Copy>(all: &[T], name: &str, name_of: fn(&T) -> &str) -> Result<T, String> {
+    all.iter()
+        .find(|item| name_of(item) == name)
+        .copied()
+        .ok_or_else(|| format!("unknown name `{}`", name))
+}
+
+/// Size of a rendered room relative to the default of `CELL_SIZE` pixels per tile.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Scale {
+    /// Every tile is drawn this many times larger, slopes are rasterized at
+    /// the larger size so their edges stay smooth.
+    Pixels(u32),
+    /// Thumbnail where every output pixel covers this many tiles in each
+    /// direction, can be fractional but must be greater than zero.
+    TilesPerPixel(f32),
+}
+
+impl Default for Scale {
+    fn default() -> Self {
+        Scale::Pixels(1)
+    }
+}
+
+impl Scale {
+    /// Size of an image of `width` by `height` pixels after scaling, at least 1x1.
+    pub fn apply(&self, width: u32, height: u32) -> (u32, u32) {
+        match *self {
+            Scale::Pixels(factor) => (width * factor.max(1), height * factor.max(1)),
+            Scale::TilesPerPixel(tiles) => {
+                let pixels = CELL_SIZE as f32 * tiles;
+                let scaled = |size: u32| ((size as f32 / pixels).round() as u32).max(1);
+                (scaled(width), scaled(height))
+            }
+        }
+    }
+
+    /// Where pixel `x` of an unscaled image ends up after scaling.
+    pub fn apply_offset(&self, x: u32) -> u32 {
+        match *self {
+            Scale::Pixels(factor) => x * factor.max(1),
+            Scale::TilesPerPixel(tiles) => (x as f32 / (CELL_SIZE as f32 * tiles)).round() as u32,
+        }
+    }
+}
+
+/// Resizes a rendered room, nearest neighbour when enlarging so every pixel
+/// of the collision stays a sharp square, averaged when shrinking.
+pub fn scale_image(img: &RgbaImage, scale: Scale) -> RgbaImage {
+    let (width, height) = scale.apply(img.width(), img.height());
+    if (width, height) == img.dimensions() {
+        return img.clone();
+    }
+
+    match scale {
+        Scale::Pixels(_) => imageops::resize(img, width, height, imageops::FilterType::Nearest),
+        Scale::TilesPerPixel(_) => imageops::thumbnail(img, width, height),
+    }
+}
+
+/// Draws a single cell at tile position `x`, `y` with the given style.
+pub fn draw_block(img: &mut RgbaImage, x: u16, y: u16, style: &BlockStyle) {
+    let size = CELL_SIZE as u32;
+    let x0 = x as u32 * size;
+    let y0 = y as u32 * size;
+
+    for py in 0..size {
+        for px in 0..size {
+            let border = px == 0 || py == 0 || px == size - 1 || py == size - 1;
+            let draw = match style.pattern {
+                Pattern::None => false,
+                Pattern::Fill => true,
+                Pattern::Outline => border,
+                Pattern::Hatch => border || (px + py).is_multiple_of(4),
+                Pattern::CrossHatch => {
+                    border || (px + py).is_multiple_of(4) || (size + px - py).is_multiple_of(4)
+                }
+                Pattern::HorizontalLines => border || py.is_multiple_of(4),
+                Pattern::Cross => border || px == py || px + py == size - 1,
+            };
+
+            if draw && x0 + px < img.width() && y0 + py < img.height() {
+                img.put_pixel(x0 + px, y0 + py, style.color);
+            }
+        }
+    }
+}
+
+// 3x5 pixel glyphs, one row per byte with the leftmost pixel in bit 2
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ' ' => [0b000; 5],
+        _ => [0b111, 0b001, 0b010, 0b000, 0b010],
+    }
+}
+
+/// Size in pixels of `text` drawn by `draw_text`.
+pub fn text_size(text: &str, scale: u32) -> (u32, u32) {
+    let len = text.chars().count() as u32;
+    ((len * 4).saturating_sub(1) * scale, 5 * scale)
+}
+
+/// Draws `text` with a 3x5 pixel font, every font pixel `scale` pixels wide,
+/// with the top left corner at pixel `x`, `y`.
+pub fn draw_text(img: &mut RgbaImage, x: u32, y: u32, text: &str, scale: u32, color: Rgba<u8>) {
+    for (i, c) in text.chars().enumerate() {
+        let x0 = x + i as u32 * 4 * scale;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) == 0 {
+                    continue;
+                }
+
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let px = x0 + column * scale + dx;
+                        let py = y + row as u32 * scale + dy;
+                        if px < img.width() && py < img.height() {
+                            img.put_pixel(px, py, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Draws the PLMs whose category has a colour in `style`, with a glyph per
+/// category and, with `RenderStyle::plm_labels`, their label under it.
+pub fn draw_plms(img: &mut RgbaImage, plms: &[Plm], style: &RenderStyle) {
+    let size = CELL_SIZE as u32;
+
+    for plm in plms {
+        let category = plm.category();
+        let Some(&color) = style.plms.get(&category) else {
+            continue;
+        };
+
+        let x = plm.x as u32 * size;
+        let y = plm.y as u32 * size;
+        match category {
+            PlmCategory::Item => draw_filled_circle_mut(
+                img,
+                ((x + size / 2) as i32, (y + size / 2) as i32),
+                5,
+                color,
+            ),
+            PlmCategory::Door => draw_filled_rect_mut(
+                img,
+                Rect::at(x as i32 + 5, y as i32).of_size(6, size),
+                color,
+            ),
+            PlmCategory::Gate => draw_filled_rect_mut(
+                img,
+                Rect::at(x as i32 + 7, y as i32).of_size(2, size),
+                color,
+            ),
+            PlmCategory::Scroll => draw_hollow_rect_mut(
+                img,
+                Rect::at(x as i32 + 2, y as i32 + 2).of_size(size - 4, size - 4),
+                color,
+            ),
+            PlmCategory::Other => {
+                let (x0, y0) = (x as f32, y as f32);
+                let end = (size - 1) as f32;
+                draw_line_segment_mut(img, (x0, y0), (x0 + end, y0 + end), color);
+                draw_line_segment_mut(img, (x0 + end, y0), (x0, y0 + end), color);
+            }
+        }
+
+        if style.plm_labels {
+            // under the glyph, or above it in the bottom row of the room
+            let (_, label_height) = text_size(&plm.label(), 1);
+            let label_y = if y + size + 1 + label_height <= img.height() {
+                y + size + 1
+            } else {
+                y.saturating_sub(label_height + 1)
+            };
+            draw_text(img, x, label_y, &plm.label(), 1, color);
+        }
+    }
+}
+
+/// Draws `value` as two hex digits, 7x5 pixels with the top left corner at pixel `x`, `y`.
+pub fn draw_hex_byte(img: &mut RgbaImage, x: u32, y: u32, value: u8, color: Rgba<u8>) {
+    draw_text(img, x, y, &format!("{:02X}", value), 1, color);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        state::{RoomState, StateCondition, STATE_SIZE},
+        types::{room_bytes, room_from, Flip, Room},
+    };
+
+    #[test]
+    fn scale_sizes() {
+        assert_eq!(Scale::Pixels(4).apply(256, 512), (1024, 2048));
+        assert_eq!(Scale::TilesPerPixel(1.0).apply(256, 512), (16, 32));
+        assert_eq!(Scale::TilesPerPixel(0.5).apply(256, 512), (32, 64));
+        assert_eq!(Scale::TilesPerPixel(64.0).apply(256, 512), (1, 1));
+        assert_eq!(Scale::Pixels(4).apply_offset(256), 1024);
+        assert_eq!(Scale::TilesPerPixel(0.5).apply_offset(256), 32);
+        assert_eq!(Scale::TilesPerPixel(64.0).apply_offset(256), 0);
+    }
+
+    #[test]
+    fn upscaling_keeps_pixels_sharp() {
+        let mut img = RgbaImage::new(2, 1);
+        img.put_pixel(1, 0, Rgba([255, 0, 0, 255]));
+
+        let scaled = scale_image(&img, Scale::Pixels(3));
+        assert_eq!(scaled.dimensions(), (6, 3));
+        assert_eq!(scaled.get_pixel(2, 2), &Rgba([0, 0, 0, 0]));
+        assert_eq!(scaled.get_pixel(3, 0), &Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn theme_round_trips_through_the_default_style() {
+        for style in [RenderStyle::default(), RenderStyle::debug_random(42)] {
+            let mut parsed = RenderStyle::default();
+            parsed.apply_theme(&style.to_theme()).unwrap();
+            assert_eq!(parsed, style);
+        }
+    }
+
+    #[test]
+    fn debug_random_depends_only_on_the_seed() {
+        assert_eq!(RenderStyle::debug_random(1), RenderStyle::debug_random(1));
+        assert_ne!(RenderStyle::debug_random(1), RenderStyle::debug_random(2));
+    }
+
+    #[test]
+    fn theme_errors_point_at_the_line() {
+        let mut style = RenderStyle::default();
+        let theme = "# comment\n\nblock.spike = #102030 fill\nslope.slope_45 = red\n";
+        match style.apply_theme(theme) {
+            Err(ThemeError::InvalidLine { line, .. }) => assert_eq!(line, 4),
+            result => panic!("unexpected {:?}", result),
+        }
+        assert_eq!(
+            style.block(BlockType::Spike),
+            Some(&BlockStyle::new(
+                Rgba([0x10, 0x20, 0x30, 255]),
+                Pattern::Fill
+            ))
+        );
+    }
+
+    #[test]
+    fn layer2_is_drawn_under_the_collision() {
+        // a single screen room with a solid floor and layer 2 tile 5 everywhere
+        let mut bytes = room_bytes(|_, y| if y == 15 { (0x8000, 0) } else { (0, 0) });
+        for _ in 0..256 {
+            bytes.extend_from_slice(&0x0405u16.to_le_bytes());
+        }
+        let room = Room::try_from_bytes(&bytes).unwrap();
+        assert_eq!(room.layer2.len(), 256);
+        let tile = room.get_layer2_tile(3, 4).unwrap();
+        assert_eq!((tile.x(), tile.y(), tile.tile()), (3, 4, 5));
+        assert_eq!(tile.flip(), Flip::Horizontal);
+
+        let mut style = RenderStyle::default();
+        assert_eq!(room.render_image_with_style(&style).get_pixel(40, 40)[3], 0);
+
+        style.layer2 = Some(Rgba([100, 100, 100, 128]));
+        let img = room.render_image_with_style(&style);
+        assert_eq!(img.get_pixel(40, 40)[3], 128);
+        assert_eq!(*img.get_pixel(40, 250), SOLID_COLOR);
+
+        // the fade survives scaling
+        let scaled = room.render_image_scaled(&style, Scale::Pixels(2));
+        assert_eq!(scaled.get_pixel(80, 80)[3], 128);
+        assert_eq!(*scaled.get_pixel(80, 500), SOLID_COLOR);
+    }
+
+    #[test]
+    fn plm_overlay_draws_enabled_categories() {
+        let mut room = room_from(|_, _| (0, 0));
+
+        let mut state = RoomState::from_bytes(StateCondition::Default, 0, &[0; STATE_SIZE]);
+        state.plms = vec![
+            Plm::from_bytes(&[0xDB, 0xEE, 2, 2, 0, 0]),
+            Plm::from_bytes(&[0x42, 0xC8, 8, 2, 0, 0]),
+        ];
+        assert_eq!(state.plms[0].category(), PlmCategory::Item);
+        assert_eq!(state.plms[0].label(), "MISSILE");
+        assert_eq!(state.plms[1].category(), PlmCategory::Door);
+        room.states.push(state);
+
+        let item_color = Rgba([0, 255, 255, 255]);
+        let mut style = RenderStyle::default();
+        style.plms.insert(PlmCategory::Item, item_color);
+        let img = room.render_image_with_style(&style);
+        assert_eq!(*img.get_pixel(40, 40), item_color);
+        assert_eq!(img.get_pixel(8 * 16 + 8, 40)[3], 0);
+        assert!((48..54).all(|y| img.get_pixel(32, y)[3] == 0));
+
+        style.plm_labels = true;
+        let img = room.render_image_with_style(&style);
+        assert!((48..54).any(|y| *img.get_pixel(32, y) == item_color));
+
+        let mut parsed = RenderStyle::default();
+        parsed.apply_theme(&style.to_theme()).unwrap();
+        assert_eq!(parsed, style);
+    }
+}
@@ -0,0 +1,616 @@
+//! Reads rooms straight from a Super Metroid ROM image.
+//!
+//! The `.room` files this crate started with are the first 15 bytes of a room
+//! header, with the area and room index swapped, followed by the decompressed
+//! level data without its 2 byte size prefix. `Rom::room_bytes` builds the same
+//! layout, so rooms from the ROM parse exactly like the extracted files.
+
+use std::path::Path;
+
+use log::{debug, warn};
+
+use crate::{
+    constants::ROOM_HEADER_SIZE,
+    error::RomError,
+    lz,
+    state::{
+        Door, Enemy, Fx, Plm, RoomState, StateCondition, DOOR_SIZE, ENEMY_POPULATION_BANK,
+        ENEMY_SIZE, FX_BANK, FX_SIZE, PLM_BANK, PLM_SIZE, STATE_SIZE,
+    },
+    types::Room,
+};
+
+/// Size of the copier header some ROM dumps start with.
+pub const COPIER_HEADER_SIZE: usize = 0x200;
+/// Size of a LoROM bank, as mapped to `$8000..=$FFFF`.
+pub const BANK_SIZE: usize = 0x8000;
+
+/// Bank of the room headers, state lists and door lists.
+pub const ROOM_BANK: u8 = 0x8F;
+/// Bank of the door data the door lists point to.
+pub const DOOR_BANK: u8 = 0x83;
+
+/// Rooms the game enters without a door, the walk over the door lists starts here.
+pub const START_ROOMS: [u32; 2] = [
+    0x8F91F8, // landing site
+    0x8FDF45, // ceres elevator room
+];
+
+/// Converts a LoROM address to an offset into an unheadered ROM image.
+///
+/// Returns `None` for addresses below `$8000` in their bank, which are not ROM.
+pub fn lorom_to_pc(address: u32) -> Option<usize> {
+    let bank = (address >> 16) & 0x7F;
+    let offset = address & 0xFFFF;
+    if offset < 0x8000 {
+        return None;
+    }
+
+    Some((bank as usize) * BANK_SIZE + (offset as usize - 0x8000))
+}
+
+/// Converts an offset into an unheadered ROM image to its LoROM address in the `$80+` banks.
+pub fn pc_to_lorom(offset: usize) -> u32 {
+    let bank = (offset / BANK_SIZE) as u32 | 0x80;
+    (bank << 16) | (offset % BANK_SIZE) as u32 | 0x8000
+}
+
+// a full address in `bank` from a 16 bit pointer
+fn long_address(bank: u8, pointer: u16) -> u32 {
+    (bank as u32) << 16 | pointer as u32
+}
+
+/// The parts of a room header needed to find the rest of the room.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoomHeader {
+    pub address: u32,
+    pub room_index: u8,
+    pub area_index: u8,
+    pub map_x: u8,
+    pub map_y: u8,
+    /// Width in screens.
+    pub width: u8,
+    /// Height in screens.
+    pub height: u8,
+    /// Address of the list of door pointers.
+    pub door_list: u32,
+    /// The conditions of the state list with the address of their state data,
+    /// ending with `StateCondition::Default`.
+    pub states: Vec<(StateCondition, u32)>,
+}
+
+impl RoomHeader {
+    /// Address of the state used when no state condition applies.
+    pub fn default_state(&self) -> Result<u32, RomError> {
+        // the state list always ends with the default state
+        match self.states.last() {
+            Some((StateCondition::Default, address)) => Ok(*address),
+            _ => Err(RomError::NoDefaultState { room: self.address }),
+        }
+    }
+}
+
+/// A Super Metroid ROM image, with the copier header removed.
+#[derive(Debug, Clone)]
+pub struct Rom {
+    data: Vec<u8>,
+}
+
+impl Rom {
+    /// Takes a ROM image, headered or not.
+    pub fn from_bytes(mut bytes: Vec<u8>) -> Result<Self, RomError> {
+        match bytes.len() % BANK_SIZE {
+            0 if !bytes.is_empty() => {}
+            COPIER_HEADER_SIZE => {
+                bytes.drain(..COPIER_HEADER_SIZE);
+            }
+            _ => return Err(RomError::InvalidSize { len: bytes.len() }),
+        }
+
+        Ok(Rom { data: bytes })
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, RomError> {
+        Rom::from_bytes(std::fs::read(path)?)
+    }
+
+    /// The ROM image without the copier header.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Everything from `address` to the end of the image.
+    ///
+    /// LoROM banks are contiguous in the image, so data crossing a bank boundary
+    /// simply continues in the slice.
+    pub fn slice_from(&self, address: u32) -> Result<&[u8], RomError> {
+        lorom_to_pc(address)
+            .and_then(|offset| self.data.get(offset..))
+            .filter(|data| !data.is_empty())
+            .ok_or(RomError::AddressOutOfRange { address })
+    }
+
+    pub fn read(&self, address: u32, len: usize) -> Result<&[u8], RomError> {
+        self.slice_from(address)?
+            .get(..len)
+            .ok_or(RomError::AddressOutOfRange { address })
+    }
+
+    pub fn read_u8(&self, address: u32) -> Result<u8, RomError> {
+        Ok(self.read(address, 1)?[0])
+    }
+
+    pub fn read_u16(&self, address: u32) -> Result<u16, RomError> {
+        let bytes = self.read(address, 2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn read_u24(&self, address: u32) -> Result<u32, RomError> {
+        let bytes = self.read(address, 3)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+    }
+
+    /// Parses the room header at `address` and finds its default state.
+    pub fn room_header(&self, address: u32) -> Result<RoomHeader, RomError> {
+        let bytes = self.read(address, 11)?;
+
+        // the state list follows the header, the default state ends it and
+        // its data follows directly, every other entry points to its data
+        let mut states = Vec::new();
+        let mut entry = address + 11;
+        loop {
+            let code = self.read_u16(entry)?;
+            let size =
+                StateCondition::parameter_size(code).ok_or(RomError::UnknownStateCondition {
+                    room: address,
+                    address: entry,
+                    code,
+                })?;
+            let parameter = self.read(entry + 2, size)?;
+            let condition = StateCondition::from_code(code, parameter).ok_or(
+                RomError::UnknownStateCondition {
+                    room: address,
+                    address: entry,
+                    code,
+                },
+            )?;
+            entry += 2 + size as u32;
+
+            if condition == StateCondition::Default {
+                states.push((condition, entry));
+                break;
+            }
+            states.push((condition, long_address(ROOM_BANK, self.read_u16(entry)?)));
+            entry += 2;
+        }
+
+        Ok(RoomHeader {
+            address,
+            room_index: bytes[0],
+            area_index: bytes[1],
+            map_x: bytes[2],
+            map_y: bytes[3],
+            width: bytes[4],
+            height: bytes[5],
+            door_list: long_address(ROOM_BANK, u16::from_le_bytes([bytes[9], bytes[10]])),
+            states,
+        })
+    }
+
+    /// The decompressed level data of the default state, starting with the
+    /// 2 byte size of the level words.
+    pub fn level_data(&self, header: &RoomHeader) -> Result<Vec<u8>, RomError> {
+        let address = self.read_u24(header.default_state()?)?;
+        lz::decompress(self.slice_from(address)?)
+            .map_err(|error| RomError::Decompression { address, error })
+    }
+
+    /// The room at `address` in the layout of an extracted `.room` file.
+    pub fn room_bytes(&self, address: u32) -> Result<Vec<u8>, RomError> {
+        self.header_room_bytes(&self.room_header(address)?)
+    }
+
+    fn header_room_bytes(&self, header: &RoomHeader) -> Result<Vec<u8>, RomError> {
+        let raw_header = self.read(header.address, ROOM_HEADER_SIZE)?;
+        let level_data = self.level_data(header)?;
+
+        let mut bytes = Vec::with_capacity(ROOM_HEADER_SIZE + level_data.len());
+        bytes.extend_from_slice(&[raw_header[1], raw_header[0]]);
+        bytes.extend_from_slice(&raw_header[2..]);
+        bytes.extend_from_slice(level_data.get(2..).unwrap_or_default());
+        Ok(bytes)
+    }
+
+    /// Parses the room at `address` with its states and doors, its `room_id`
+    /// is the area and the room index in hex, e.g. `brinstar_05`, as room
+    /// indices restart in every area.
+    ///
+    /// The cells are those of the default state.
+    pub fn room(&self, address: u32) -> Result<Room, RomError> {
+        let header = self.room_header(address)?;
+        let mut room =
+            Room::try_from_bytes(&self.header_room_bytes(&header)?).map_err(|error| {
+                RomError::Room {
+                    room: address,
+                    error,
+                }
+            })?;
+        room.room_id = format!("{}_{:02X}", room.area_index(), room.room_index());
+        room.states = self.room_states(&header)?;
+        room.doors = self.doors(header.door_list);
+        Ok(room)
+    }
+
+    /// The doors of the door list at `address`.
+    ///
+    /// Door lists have no terminator, the list ends at the first entry that
+    /// isn't a pointer into `DOOR_BANK`.
+    pub fn doors(&self, address: u32) -> Vec<Door> {
+        let mut doors = Vec::new();
+
+        for entry in (address..).step_by(2) {
+            let Ok(pointer) = self.read_u16(entry) else {
+                break;
+            };
+            if pointer < 0x8000 {
+                break;
+            }
+            let door = long_address(DOOR_BANK, pointer);
+            let Ok(bytes) = self.read(door, DOOR_SIZE) else {
+                break;
+            };
+            doors.push(Door::from_bytes(door, bytes));
+        }
+
+        doors
+    }
+
+    /// The destination rooms of the doors in the door list at `address`,
+    /// elevator doors without a destination are left out.
+    pub fn door_destinations(&self, address: u32) -> Vec<u32> {
+        self.doors(address)
+            .into_iter()
+            .filter(|door| door.destination >= 0x8000)
+            .map(|door| long_address(ROOM_BANK, door.destination))
+            .collect()
+    }
+
+    /// The PLM set at `pointer` in `PLM_BANK`, which ends with a zero id.
+    pub fn plms(&self, pointer: u16) -> Result<Vec<Plm>, RomError> {
+        let mut plms = Vec::new();
+        if pointer < 0x8000 {
+            return Ok(plms);
+        }
+
+        let mut entry = long_address(PLM_BANK, pointer);
+        while self.read_u16(entry)? != 0 {
+            plms.push(Plm::from_bytes(self.read(entry, PLM_SIZE)?));
+            entry += PLM_SIZE as u32;
+        }
+
+        Ok(plms)
+    }
+
+    /// The enemy population at `pointer` in `ENEMY_POPULATION_BANK` and the
+    /// number of enemies to kill stored after its `0xFFFF` terminator.
+    pub fn enemies(&self, pointer: u16) -> Result<(Vec<Enemy>, u8), RomError> {
+        let mut enemies = Vec::new();
+        if pointer < 0x8000 {
+            return Ok((enemies, 0));
+        }
+
+        let mut entry = long_address(ENEMY_POPULATION_BANK, pointer);
+        while self.read_u16(entry)? != 0xFFFF {
+            enemies.push(Enemy::from_bytes(self.read(entry, ENEMY_SIZE)?));
+            entry += ENEMY_SIZE as u32;
+        }
+
+        Ok((enemies, self.read_u8(entry + 2)?))
+    }
+
+    /// The FX entries at `pointer` in `FX_BANK`.
+    ///
+    /// Entries for specific doors come first, the list ends after the entry
+    /// for every door or at a `0xFFFF` door.
+    pub fn fx(&self, pointer: u16) -> Result<Vec<Fx>, RomError> {
+        let mut fx = Vec::new();
+        if pointer < 0x8000 {
+            return Ok(fx);
+        }
+
+        let mut entry = long_address(FX_BANK, pointer);
+        loop {
+            let door = self.read_u16(entry)?;
+            if door == 0xFFFF {
+                break;
+            }
+            fx.push(Fx::from_bytes(self.read(entry, FX_SIZE)?));
+            if door == 0 {
+                break;
+            }
+            entry += FX_SIZE as u32;
+        }
+
+        Ok(fx)
+    }
+
+    /// Every state of the room, in the order of the state list, with their objects.
+    ///
+    /// An object list that can't be read is logged and left empty, the room
+    /// is still usable without it.
+    pub fn room_states(&self, header: &RoomHeader) -> Result<Vec<RoomState>, RomError> {
+        header
+            .states
+            .iter()
+            .map(|(condition, address)| {
+                let mut state =
+                    RoomState::from_bytes(*condition, *address, self.read(*address, STATE_SIZE)?);
+                let unreadable = |list: &str, error: RomError| {
+                    warn!(
+                        "room ${:06X}: cannot read the {} of the state at ${:06X}: {}",
+                        header.address, list, address, error
+                    );
+                };
+                state.fx = self.fx(state.fx_pointer).unwrap_or_else(|error| {
+                    unreadable("fx", error);
+                    Vec::new()
+                });
+                (state.enemies, state.enemies_to_kill) = self
+                    .enemies(state.enemy_population_pointer)
+                    .unwrap_or_else(|error| {
+                        unreadable("enemies", error);
+                        (Vec::new(), 0)
+                    });
+                state.plms = self.plms(state.plm_set_pointer).unwrap_or_else(|error| {
+                    unreadable("plms", error);
+                    Vec::new()
+                });
+                Ok(state)
+            })
+            .collect()
+    }
+
+    /// Addresses of every room reachable through doors from `START_ROOMS`, sorted.
+    ///
+    /// The game has no table of all rooms, so this is the same walk extraction
+    /// tools do. Destinations without a valid header are skipped.
+    pub fn room_addresses(&self) -> Vec<u32> {
+        let mut found: Vec<u32> = Vec::new();
+        let mut queue: Vec<u32> = START_ROOMS.to_vec();
+
+        while let Some(address) = queue.pop() {
+            if found.contains(&address) {
+                continue;
+            }
+
+            let header = match self.room_header(address) {
+                Ok(header) if header.width > 0 && header.height > 0 => header,
+                Ok(_) => {
+                    debug!("skipping zero sized room at ${:06X}", address);
+                    continue;
+                }
+                Err(e) => {
+                    debug!("skipping room at ${:06X}: {}", address, e);
+                    continue;
+                }
+            };
+
+            found.push(address);
+            queue.extend(self.door_destinations(header.door_list));
+        }
+
+        found.sort();
+        found
+    }
+
+    /// Every room of `room_addresses`, parsed.
+    pub fn rooms(&self) -> Result<Vec<Room>, RomError> {
+        self.room_addresses()
+            .into_iter()
+            .map(|address| self.room(address))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(rom: &mut [u8], address: u32, bytes: &[u8]) {
+        let offset = lorom_to_pc(address).unwrap();
+        rom[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    // two single screen rooms linked by a door, the second one has an event state
+    fn test_rom() -> (Vec<u8>, Vec<u8>) {
+        let mut rom = vec![0; 0x48 * BANK_SIZE];
+
+        let mut level = vec![0x00, 0x02];
+        for i in 0..256u16 {
+            level.extend_from_slice(&(if i >= 240 { 0x8000u16 } else { 0 }).to_le_bytes());
+        }
+        level.extend([0; 256]);
+        write(&mut rom, 0xC48000, &lz::compress(&level));
+
+        // the default state of both rooms, with one fx entry, enemy and plm
+        let mut state = vec![0x00, 0x80, 0xC4];
+        state.resize(STATE_SIZE, 0);
+        state[0x6..0x8].copy_from_slice(&0xA100u16.to_le_bytes());
+        state[0x8..0xA].copy_from_slice(&0x8100u16.to_le_bytes());
+        state[0x14..0x16].copy_from_slice(&0xA200u16.to_le_bytes());
+        write(&mut rom, 0x83A100, &[0, 0, 0x40, 0x01, 0, 0, 0, 0, 0, 0x06]);
+        write(&mut rom, 0xA18100, &[0xBF, 0xCE, 0x80, 0x00, 0xB0, 0x00]);
+        write(&mut rom, 0xA18110, &[0xFF, 0xFF, 0x01]);
+        write(
+            &mut rom,
+            0x8FA200,
+            &[0xDB, 0xEE, 0x05, 0x0C, 0x10, 0x00, 0x00, 0x00],
+        );
+
+        let mut landing_site = vec![0x00, 0x00, 0x17, 0x00, 0x01, 0x01, 0x70, 0xA0, 0x00];
+        landing_site.extend([0x00, 0xA0, 0xE6, 0xE5]);
+        landing_site.extend(&state);
+        write(&mut rom, 0x8F91F8, &landing_site);
+
+        // one door to the second room, the next word ends the list
+        write(&mut rom, 0x8FA000, &[0x00, 0x90, 0x01, 0x00]);
+        write(&mut rom, 0x839000, &[0x00, 0x93]);
+
+        let mut second = vec![0x05, 0x01, 0x02, 0x03, 0x01, 0x01, 0x70, 0xA0, 0x00];
+        second.extend([0x10, 0xA0, 0x12, 0xE6, 0x0E, 0x00, 0xB0, 0xE6, 0xE5]);
+        second.extend(&state);
+        write(&mut rom, 0x8F9300, &second);
+
+        let mut expected = vec![0x00, 0x00, 0x17, 0x00, 0x01, 0x01, 0x70, 0xA0, 0x00];
+        expected.extend([0x00, 0xA0, 0xE6, 0xE5, 0x00, 0x80]);
+        expected.extend(&level[2..]);
+
+        (rom, expected)
+    }
+
+    #[test]
+    fn lorom_addresses() {
+        assert_eq!(lorom_to_pc(0x808000), Some(0));
+        assert_eq!(lorom_to_pc(0x8F91F8), Some(0x791F8));
+        assert_eq!(lorom_to_pc(0x8F1000), None);
+        assert_eq!(pc_to_lorom(0x791F8), 0x8F91F8);
+    }
+
+    #[test]
+    fn rooms_match_the_extracted_layout() {
+        let (rom, expected) = test_rom();
+        let rom = Rom::from_bytes(rom).unwrap();
+
+        assert_eq!(rom.room_addresses(), vec![0x8F91F8, 0x8F9300]);
+        assert_eq!(rom.room_bytes(0x8F91F8).unwrap(), expected);
+
+        let room = rom.room(0x8F91F8).unwrap();
+        assert_eq!(
+            room.to_bytes(),
+            Room::try_from_bytes(&expected).unwrap().to_bytes()
+        );
+        assert_eq!(room.room_id, "crateria_00");
+
+        let second = rom.room(0x8F9300).unwrap();
+        assert_eq!(second.room_index(), 5);
+        assert_eq!(second.room_id, "brinstar_05");
+        assert_eq!((second.map_x(), second.map_y()), (2, 3));
+    }
+
+    #[test]
+    fn level_data_is_decompressed() {
+        let (mut rom, expected) = test_rom();
+        let level = [
+            0x01,
+            0x00,
+            0x02, // direct copy of the layer 1 size
+            0xE5,
+            0xDF,
+            0x00, // 480 bytes of air
+            0x5F,
+            0x00,
+            0x80, // a row of solid blocks
+            0xE4,
+            0xFF,
+            0x00, // the bts
+            lz::END,
+        ];
+        write(&mut rom, 0xC48000, &level);
+
+        let rom = Rom::from_bytes(rom).unwrap();
+        assert_eq!(rom.room_bytes(0x8F91F8).unwrap(), expected);
+    }
+
+    #[test]
+    fn states_doors_and_objects() {
+        let (rom, _) = test_rom();
+        let rom = Rom::from_bytes(rom).unwrap();
+
+        let landing_site = rom.room(0x8F91F8).unwrap();
+        assert_eq!(landing_site.doors.len(), 1);
+        assert_eq!(landing_site.doors[0].address, 0x839000);
+        assert_eq!(landing_site.doors[0].destination, 0x9300);
+
+        let room = rom.room(0x8F9300).unwrap();
+        assert!(room.doors.is_empty());
+        let conditions: Vec<StateCondition> =
+            room.states.iter().map(|state| state.condition).collect();
+        assert_eq!(
+            conditions,
+            vec![StateCondition::Event(0x0E), StateCondition::Default]
+        );
+        assert_eq!(room.states[0].address, 0x8FB000);
+        assert!(room.states[0].plms.is_empty());
+
+        let default = &room.states[1];
+        assert_eq!(default.level_data, 0xC48000);
+        assert_eq!(
+            default.plms,
+            vec![Plm {
+                id: 0xEEDB,
+                x: 5,
+                y: 12,
+                parameter: 0x10
+            }]
+        );
+        assert_eq!(default.enemies.len(), 1);
+        assert_eq!((default.enemies[0].x, default.enemies[0].y), (0x80, 0xB0));
+        assert_eq!(default.enemies_to_kill, 1);
+        assert_eq!(default.fx.len(), 1);
+        assert_eq!((default.fx[0].base_y, default.fx[0].fx_type), (0x140, 0x06));
+    }
+
+    #[test]
+    fn unreadable_object_lists_are_left_empty() {
+        let (mut rom, expected) = test_rom();
+        // the fx and plm lists of the landing site run past the end of their banks
+        let state = 0x8F91F8 + 13;
+        write(&mut rom, state + 0x6, &0xFFFEu16.to_le_bytes());
+        write(&mut rom, state + 0x14, &0xFFFEu16.to_le_bytes());
+        write(&mut rom, 0x83FFFE, &[0x01, 0x00]);
+        write(&mut rom, 0x8FFFFE, &[0x01, 0x00]);
+        let rom = Rom::from_bytes(rom).unwrap();
+        assert!(rom.fx(0xFFFE).is_err());
+        assert!(rom.plms(0xFFFE).is_err());
+
+        let room = rom.room(0x8F91F8).unwrap();
+        assert_eq!(
+            room.to_bytes(),
+            Room::try_from_bytes(&expected).unwrap().to_bytes()
+        );
+        let default = &room.states[0];
+        assert!(default.fx.is_empty());
+        assert!(default.plms.is_empty());
+        assert_eq!(default.enemies.len(), 1);
+    }
+
+    #[test]
+    fn default_state_ends_the_state_list() {
+        let (rom, _) = test_rom();
+        let rom = Rom::from_bytes(rom).unwrap();
+        let mut header = rom.room_header(0x8F9300).unwrap();
+        assert_eq!(header.default_state().unwrap(), 0x8F9312);
+
+        header.states.pop();
+        assert!(matches!(
+            header.default_state(),
+            Err(RomError::NoDefaultState { room: 0x8F9300 })
+        ));
+        header.states.clear();
+        assert!(header.default_state().is_err());
+    }
+
+    #[test]
+    fn copier_header_is_skipped() {
+        let (rom, expected) = test_rom();
+        let mut headered = vec![0; COPIER_HEADER_SIZE];
+        headered.extend(rom);
+
+        let rom = Rom::from_bytes(headered).unwrap();
+        assert_eq!(rom.room_bytes(0x8F91F8).unwrap(), expected);
+        assert!(matches!(
+            Rom::from_bytes(vec![0; 0x100]),
+            Err(RomError::InvalidSize { len: 0x100 })
+        ));
+    }
+}
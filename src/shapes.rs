@@ -1,9 +1,9 @@
-use babel::*;
 use imageproc::point::Point;
 
 use crate::constants::CELL_SIZE;
 use crate::types::SlopeType;
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Polygon {
     pub points: Vec<Point<i32>>,
 }
@@ -13,6 +13,11 @@ impl Polygon {
         Polygon { points }
     }
 
+    /// A polygon covering a whole cell.
+    pub fn square() -> Polygon {
+        shape_from_heights(&babel::SLOPE_SQUARE).remove(0)
+    }
+
     pub fn mirror_x(&mut self) {
         for point in &mut self.points {
             point.x = (-point.x) + CELL_SIZE as i32 - 1;
@@ -84,10 +89,99 @@ impl Polygon {
         }
     }
 
+    /// Removes repeated points and points in the middle of a straight edge.
+    ///
+    /// Points where the outline doubles back on itself are kept, they are
+    /// the tip of a one pixel wide spike.
     pub fn symplify(&mut self) {
-        // check if a point already exists
-        // check if a point is on the same line as the previous and next point
-        // if so, remove the point
+        loop {
+            let len = self.points.len();
+            if len < 3 {
+                // only repeated points can go
+                self.points.dedup();
+                return;
+            }
+
+            // check if a point already exists
+            // check if a point is on the same line as the previous and next point
+            let keep: Vec<bool> = (0..len)
+                .map(|i| {
+                    let a = self.points[(i + len - 1) % len];
+                    let b = self.points[i];
+                    let c = self.points[(i + 1) % len];
+                    let (dx1, dy1) = (b.x - a.x, b.y - a.y);
+                    let (dx2, dy2) = (c.x - b.x, c.y - b.y);
+                    let repeated = dx1 == 0 && dy1 == 0;
+                    let straight = dx1 * dy2 - dy1 * dx2 == 0 && dx1 * dx2 + dy1 * dy2 > 0;
+                    !repeated && !straight
+                })
+                .collect();
+
+            if keep.iter().all(|keep| *keep) {
+                return;
+            }
+            if keep.iter().all(|keep| !*keep) {
+                // every point is the same
+                self.points.truncate(1);
+                return;
+            }
+
+            // if so, remove the point
+            let mut keep = keep.into_iter();
+            self.points.retain(|_| keep.next().unwrap());
+        }
+    }
+
+    /// `symplify`, then Douglas-Peucker with the given tolerance in pixels.
+    ///
+    /// A tolerance of 0 only removes repeated and collinear points. The outline
+    /// is never reduced below three points.
+    pub fn symplify_with_tolerance(&mut self, tolerance: f32) {
+        self.symplify();
+
+        let len = self.points.len();
+        if tolerance <= 0.0 || len <= 3 {
+            return;
+        }
+
+        // split the ring at the point farthest from the first one and
+        // simplify both halves as open chains
+        let first = self.points[0];
+        let far = (1..len)
+            .max_by_key(|i| {
+                let point = self.points[*i];
+                (point.x - first.x).pow(2) + (point.y - first.y).pow(2)
+            })
+            .unwrap();
+
+        let mut keep = vec![false; len];
+        keep[0] = true;
+        keep[far] = true;
+        let chains: [Vec<usize>; 2] = [(0..=far).collect(), (far..=len).map(|i| i % len).collect()];
+        for chain in chains {
+            douglas_peucker(&self.points, &chain, tolerance, &mut keep);
+        }
+
+        if keep.iter().filter(|keep| **keep).count() < 3 {
+            return;
+        }
+
+        let mut keep = keep.into_iter();
+        self.points.retain(|_| keep.next().unwrap());
+        self.symplify();
+    }
+
+    /// Shoelace area, positive when the points wind clockwise on screen.
+    pub fn signed_area(&self) -> f32 {
+        let len = self.points.len();
+        let twice_area: i64 = (0..len)
+            .map(|i| {
+                let a = self.points[i];
+                let b = self.points[(i + 1) % len];
+                a.x as i64 * b.y as i64 - b.x as i64 * a.y as i64
+            })
+            .sum();
+        twice_area as f32 / 2.0
     }
 
     pub fn translate(&mut self, x: f32, y: f32) {
@@ -96,254 +190,226 @@ impl Polygon {
             point.y += y as i32;
         }
     }
-}
 
-impl From<SlopeType> for Polygon {
-    fn from(slope: SlopeType) -> Polygon {
-        match slope {
-            SlopeType::HalfSolidH => Polygon::new(babel::SLOPE_HALF_SOLIDH.to_vec()),
-            SlopeType::HalfSolidV => Polygon::new(babel::SLOPE_HALF_SOLIDV.to_vec()),
-            SlopeType::SmallTriangle => Polygon::new(babel::SLOPE_SMALL_TRIANGLE.to_vec()),
-            SlopeType::BigTriangle => Polygon::new(babel::SLOPE_BIG_TRIANGLE.to_vec()),
-            SlopeType::HalfPlat => Polygon::new(babel::SLOPE_HALF_PLAT.to_vec()),
-            SlopeType::Slope45 => Polygon::new(SLOPE_45.to_vec()),
-            SlopeType::Square => Polygon::new(SLOPE_SQUARE.to_vec()),
-            SlopeType::HillPart1 => Polygon::new(SLOPE_HILL_PART1.to_vec()),
-            SlopeType::HillPart2 => Polygon::new(SLOPE_HILL_PART2.to_vec()),
-            SlopeType::SmoothHillPart1 => Polygon::new(SLOPE_SMOOTH_HILL_PART1.to_vec()),
-            SlopeType::SmoothHillPart2 => Polygon::new(SLOPE_SMOOTH_HILL_PART2.to_vec()),
-            SlopeType::SmootherHillPart1 => Polygon::new(SLOPE_SMOOTHER_HILL_PART1.to_vec()),
-            SlopeType::SmootherHillPart2 => Polygon::new(SLOPE_SMOOTHER_HILL_PART2.to_vec()),
-            SlopeType::SmootherHillPart3 => Polygon::new(SLOPE_SMOOTHER_HILL_PART3.to_vec()),
-            SlopeType::SteepHillPart1 => Polygon::new(SLOPE_STEEP_HILL_PART1.to_vec()),
-            SlopeType::SteepHillPart2 => Polygon::new(SLOPE_STEEP_HILL_PART2.to_vec()),
-            _ => Polygon::new(Vec::new()),
+    /// Moves points in inclusive pixel coordinates onto the pixel edges, so the
+    /// shape covers its last column and row instead of ending one pixel short.
+    ///
+    /// The filled side is the inside of a clockwise outline, which is also the
+    /// outside of the counter-clockwise holes of `contours::collision_contours`.
+    pub fn widen(&mut self) {
+        // the sign of the normal of an edge, pointing away from the filled side
+        let outward = |a: Point<i32>, b: Point<i32>| ((b.y - a.y).signum(), (a.x - b.x).signum());
+
+        let len = self.points.len();
+        let mut points = Vec::with_capacity(len);
+        for i in 0..len {
+            let point = self.points[i];
+            let (in_x, in_y) = outward(self.points[(i + len - 1) % len], point);
+            let (out_x, out_y) = outward(point, self.points[(i + 1) % len]);
+
+            // the tip of a one pixel wide spike covers both edges of its pixel
+            if in_x * out_x < 0 || in_y * out_y < 0 {
+                points.push(Point::new(point.x + in_x.max(0), point.y + in_y.max(0)));
+                points.push(Point::new(point.x + out_x.max(0), point.y + out_y.max(0)));
+            } else {
+                points.push(Point::new(
+                    point.x + in_x.max(out_x).max(0),
+                    point.y + in_y.max(out_y).max(0),
+                ));
+            }
         }
+        points.dedup();
+        self.points = points;
     }
 }
 
-#[allow(dead_code)]
-mod babel {
-    use imageproc::point::Point;
+// mark the points of `chain` needed to stay within `tolerance` of the original line
+fn douglas_peucker(points: &[Point<i32>], chain: &[usize], tolerance: f32, keep: &mut [bool]) {
+    if chain.len() < 3 {
+        return;
+    }
 
-    use crate::constants::CELL_SIZE;
+    let start = points[chain[0]];
+    let end = points[chain[chain.len() - 1]];
+    let (index, distance) = chain[1..chain.len() - 1]
+        .iter()
+        .enumerate()
+        .map(|(i, point)| (i + 1, distance_to_segment(points[*point], start, end)))
+        .fold(
+            (0, 0.0),
+            |best, current| {
+                if current.1 > best.1 {
+                    current
+                } else {
+                    best
+                }
+            },
+        );
 
-    type PolygonShape = &'static [Point<i32>];
+    if distance > tolerance {
+        keep[chain[index]] = true;
+        douglas_peucker(points, &chain[..=index], tolerance, keep);
+        douglas_peucker(points, &chain[index..], tolerance, keep);
+    }
+}
 
-    pub const SLOPE_HALF_SOLIDH: [Point<i32>; 4] = [
-        Point {
-            x: 0,
-            y: CELL_SIZE as i32 / 2,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: CELL_SIZE as i32 / 2,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: CELL_SIZE as i32 - 1,
-        },
-        Point {
-            x: 0,
-            y: CELL_SIZE as i32 - 1,
-        },
-    ];
+fn distance_to_segment(point: Point<i32>, start: Point<i32>, end: Point<i32>) -> f32 {
+    let (px, py) = ((point.x - start.x) as f32, (point.y - start.y) as f32);
+    let (dx, dy) = ((end.x - start.x) as f32, (end.y - start.y) as f32);
+    let length_squared = dx * dx + dy * dy;
+    if length_squared == 0.0 {
+        return (px * px + py * py).sqrt();
+    }
 
-    pub const SLOPE_HALF_SOLIDV: [Point<i32>; 4] = [
-        Point {
-            x: CELL_SIZE as i32 / 2,
-            y: 0,
-        },
-        Point {
-            x: CELL_SIZE as i32 / 2,
-            y: CELL_SIZE as i32 - 1,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: CELL_SIZE as i32 - 1,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: 0,
-        },
-    ];
+    let t = ((px * dx + py * dy) / length_squared).clamp(0.0, 1.0);
+    let (cx, cy) = (px - t * dx, py - t * dy);
+    (cx * cx + cy * cy).sqrt()
+}
 
-    pub const SLOPE_SMALL_TRIANGLE: [Point<i32>; 3] = [
-        Point {
-            x: 0,
-            y: CELL_SIZE as i32 - 1,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: CELL_SIZE as i32 - 1,
-        },
-        Point {
-            x: CELL_SIZE as i32 / 2,
-            y: CELL_SIZE as i32 / 2,
-        },
-    ];
+/// The polygons covering the solid part of a slope cell.
+///
+/// Most slope types are a single polygon, columns that are empty in the
+/// slope definition split it into several.
+pub type SlopeShape = Vec<Polygon>;
+
+impl From<SlopeType> for SlopeShape {
+    fn from(slope: SlopeType) -> SlopeShape {
+        if let Some(polygons) = babel::polygons(slope) {
+            return polygons
+                .iter()
+                .map(|points| Polygon::new(points.to_vec()))
+                .collect();
+        }
+        match babel::heights(slope) {
+            Some(heights) => shape_from_heights(heights),
+            None => Vec::new(),
+        }
+    }
+}
 
-    pub const SLOPE_BIG_TRIANGLE: [Point<i32>; 3] = [
-        Point {
-            x: 0,
-            y: CELL_SIZE as i32 - 1,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: CELL_SIZE as i32 - 1,
-        },
-        Point {
-            x: CELL_SIZE as i32 / 2,
-            y: 0,
-        },
-    ];
+/// Traces one polygon per run of non-empty columns of a slope definition.
+///
+/// Points are in the same inclusive pixel coordinates as the rest of `Polygon`,
+/// and height changes of more than one pixel between columns become vertical
+/// steps, so filling the polygon covers exactly the solid pixels of each column.
+pub fn shape_from_heights(heights: &babel::SlopeHeights) -> SlopeShape {
+    let bottom = CELL_SIZE as i32 - 1;
+    let top = |column: usize| CELL_SIZE as i32 - heights[column] as i32;
+
+    column_runs(heights)
+        .into_iter()
+        .map(|(start, end)| {
+            let mut points = Vec::new();
+
+            // left wall, top edge from left to right and right wall
+            if top(start) != bottom {
+                points.push(Point::new(start as i32, bottom));
+            }
+            for column in start..=end {
+                let x = column as i32;
+                if column > start && top(column) < top(column - 1) - 1 {
+                    points.push(Point::new(x, top(column - 1)));
+                }
+                points.push(Point::new(x, top(column)));
+                if column < end && top(column + 1) > top(column) + 1 {
+                    points.push(Point::new(x, top(column + 1)));
+                }
+            }
+            if top(end) != bottom {
+                points.push(Point::new(end as i32, bottom));
+            }
 
-    pub const SLOPE_HALF_PLAT: [Point<i32>; 4] = [
-        Point {
-            x: 0,
-            y: CELL_SIZE as i32 / 2,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: CELL_SIZE as i32 / 2,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: CELL_SIZE as i32 - 1,
-        },
-        Point {
-            x: 0,
-            y: CELL_SIZE as i32 - 1,
-        },
-    ];
+            let mut polygon = Polygon::new(points);
+            polygon.symplify();
+            polygon
+        })
+        .collect()
+}
 
-    pub const SLOPE_45: [Point<i32>; 3] = [
-        Point {
-            x: 0,
-            y: CELL_SIZE as i32 - 1,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: CELL_SIZE as i32 - 1,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: 0,
-        },
-    ];
+/// First and last column of every run of non-empty columns.
+pub fn column_runs(heights: &babel::SlopeHeights) -> Vec<(usize, usize)> {
+    let mut runs = Vec::new();
+    let mut x = 0;
 
-    pub const SLOPE_SQUARE: [Point<i32>; 4] = [
-        Point { x: 0, y: 0 },
-        Point {
-            x: 0,
-            y: CELL_SIZE as i32 - 1,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: CELL_SIZE as i32 - 1,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: 0,
-        },
-    ];
+    while x < heights.len() {
+        if heights[x] == 0 {
+            x += 1;
+            continue;
+        }
 
-    pub const SLOPE_HILL_PART1: [Point<i32>; 3] = [
-        Point {
-            x: CELL_SIZE as i32 / 2,
-            y: CELL_SIZE as i32 - 1,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: CELL_SIZE as i32 - 1,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: CELL_SIZE as i32 / 2,
-        },
-    ];
+        let start = x;
+        while x < heights.len() && heights[x] != 0 {
+            x += 1;
+        }
+        runs.push((start, x - 1));
+    }
 
-    // used when there is a slope45 on the left and anything on the right
-    pub const SLOPE_HILL_PART2: [Point<i32>; 5] = [
-        Point {
-            x: 0,
-            y: CELL_SIZE as i32 / 2 - 1,
-        },
-        Point {
-            x: CELL_SIZE as i32 / 2 - 1,
-            y: 0,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: 0,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: CELL_SIZE as i32 - 1,
-        },
-        Point {
-            x: 0,
-            y: CELL_SIZE as i32 - 1,
-        },
-    ];
+    runs
+}
 
-    pub const SLOPE_SMOOTH_HILL_PART1: [Point<i32>; 3] = [
-        Point {
-            x: 0,
-            y: CELL_SIZE as i32 - 1,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: CELL_SIZE as i32 / 2,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: CELL_SIZE as i32 - 1,
-        },
-    ];
+/// Per-pixel slope definitions, one table per `SlopeType`.
+///
+/// Most tables hold the height of the solid part of every pixel column of an
+/// unflipped cell, counted up from the bottom edge. A height of 0 is an empty
+/// column and `CELL_SIZE` a fully solid one. Shapes with more than one solid
+/// part in a column are polygons instead.
+pub mod babel {
+    use imageproc::point::Point;
+
+    use crate::{constants::CELL_SIZE, types::SlopeType};
 
-    pub const SLOPE_SMOOTH_HILL_PART2: [Point<i32>; 4] = [
+    pub type SlopeHeights = [u8; 16];
+    pub type SlopePolygons = &'static [&'static [Point<i32>]];
+
+    pub const SLOPE_HALF_SOLIDH: SlopeHeights = [8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8];
+    pub const SLOPE_HALF_SOLIDV: SlopeHeights =
+        [0, 0, 0, 0, 0, 0, 0, 0, 16, 16, 16, 16, 16, 16, 16, 16];
+    pub const SLOPE_QUARTER_SOLID: SlopeHeights = [0, 0, 0, 0, 0, 0, 0, 0, 8, 8, 8, 8, 8, 8, 8, 8];
+    pub const SLOPE_STAIR_BIG_STEPS: SlopeHeights =
+        [8, 8, 8, 8, 8, 8, 8, 8, 16, 16, 16, 16, 16, 16, 16, 16];
+    pub const SLOPE_FULL_SOLID_UNUSED: SlopeHeights = [
+        16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16,
+    ];
+    pub const SLOPE_SMALL_TRIANGLE: SlopeHeights = [1, 2, 3, 4, 5, 6, 7, 8, 8, 7, 6, 5, 4, 3, 2, 1];
+    pub const SLOPE_BIG_TRIANGLE: SlopeHeights =
+        [2, 4, 6, 8, 10, 12, 14, 16, 16, 14, 12, 10, 8, 6, 4, 2];
+    // the game's collision for the half platform is the same as the bottom half solid,
+    // only the graphics differ
+    pub const SLOPE_HALF_PLAT: SlopeHeights = SLOPE_HALF_SOLIDH;
+    pub const SLOPE_SQUARE_DUPLICATE: SlopeHeights = [
+        16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16,
+    ];
+    pub const SLOPE_STAIR_SMALL_STEPS: SlopeHeights =
+        [4, 4, 4, 4, 8, 8, 8, 8, 12, 12, 12, 12, 16, 16, 16, 16];
+    pub const SLOPE_CONCAVE_TRIANGLE: SlopeHeights =
+        [0, 0, 1, 1, 2, 2, 3, 4, 5, 6, 8, 9, 11, 12, 14, 16];
+    // two strips across the cell, the lower quarter of each half
+    pub const SLOPE_HORIZONTAL_LINES_TOP: [Point<i32>; 4] = [
         Point {
             x: 0,
-            y: CELL_SIZE as i32 / 2,
+            y: CELL_SIZE as i32 / 4,
         },
         Point {
             x: CELL_SIZE as i32 - 1,
-            y: 0,
+            y: CELL_SIZE as i32 / 4,
         },
         Point {
             x: CELL_SIZE as i32 - 1,
-            y: CELL_SIZE as i32 - 1,
-        },
-        Point {
-            x: 0,
-            y: CELL_SIZE as i32 - 1,
+            y: CELL_SIZE as i32 / 2 - 1,
         },
-    ];
-
-    pub const SLOPE_SMOOTHER_HILL_PART1: [Point<i32>; 3] = [
         Point {
             x: 0,
-            y: CELL_SIZE as i32 - 1,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: CELL_SIZE as i32 * 2 / 3,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: CELL_SIZE as i32 - 1,
+            y: CELL_SIZE as i32 / 2 - 1,
         },
     ];
-
-    pub const SLOPE_SMOOTHER_HILL_PART2: [Point<i32>; 4] = [
+    pub const SLOPE_HORIZONTAL_LINES_BOTTOM: [Point<i32>; 4] = [
         Point {
             x: 0,
-            y: CELL_SIZE as i32 * 2 / 3,
+            y: CELL_SIZE as i32 * 3 / 4,
         },
         Point {
             x: CELL_SIZE as i32 - 1,
-            y: CELL_SIZE as i32 / 3,
+            y: CELL_SIZE as i32 * 3 / 4,
         },
         Point {
             x: CELL_SIZE as i32 - 1,
@@ -354,60 +420,93 @@ mod babel {
             y: CELL_SIZE as i32 - 1,
         },
     ];
-
-    pub const SLOPE_SMOOTHER_HILL_PART3: [Point<i32>; 3] = [
-        Point {
-            x: 0,
-            y: CELL_SIZE as i32 / 3,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: 0,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: CELL_SIZE as i32 - 1,
-        },
+    pub const SLOPE_HORIZONTAL_LINES: SlopePolygons =
+        &[&SLOPE_HORIZONTAL_LINES_TOP, &SLOPE_HORIZONTAL_LINES_BOTTOM];
+    pub const SLOPE_VERTICAL_LINES: SlopeHeights =
+        [0, 0, 0, 0, 16, 16, 16, 16, 0, 0, 0, 0, 16, 16, 16, 16];
+    pub const SLOPE_45: SlopeHeights = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+    pub const SLOPE_SQUARE: SlopeHeights = [
+        16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16,
     ];
-
-    pub const SLOPE_STEEP_HILL_PART1: [Point<i32>; 3] = [
-        Point {
-            x: CELL_SIZE as i32 / 2,
-            y: CELL_SIZE as i32 - 1,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: 0,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: CELL_SIZE as i32 - 1,
-        },
+    pub const SLOPE_HILL_PART1: SlopeHeights = [0, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8];
+    pub const SLOPE_HILL_PART2: SlopeHeights = [
+        9, 10, 11, 12, 13, 14, 15, 16, 16, 16, 16, 16, 16, 16, 16, 16,
     ];
-
-    pub const SLOPE_STEEP_HILL_PART2: [Point<i32>; 4] = [
-        Point {
-            x: 0,
-            y: CELL_SIZE as i32 - 1,
-        },
-        Point {
-            x: CELL_SIZE as i32 / 2,
-            y: 0,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: 0,
-        },
-        Point {
-            x: CELL_SIZE as i32 - 1,
-            y: CELL_SIZE as i32 - 1,
-        },
+    pub const SLOPE_SMOOTH_HILL_PART1: SlopeHeights =
+        [1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8];
+    pub const SLOPE_SMOOTH_HILL_PART2: SlopeHeights =
+        [9, 9, 10, 10, 11, 11, 12, 12, 13, 13, 14, 14, 15, 15, 16, 16];
+    pub const SLOPE_SMOOTHER_HILL_PART1: SlopeHeights =
+        [1, 1, 1, 2, 2, 2, 3, 3, 3, 4, 4, 4, 5, 5, 5, 6];
+    pub const SLOPE_SMOOTHER_HILL_PART2: SlopeHeights =
+        [6, 6, 7, 7, 7, 8, 8, 8, 9, 9, 9, 10, 10, 10, 11, 11];
+    pub const SLOPE_SMOOTHER_HILL_PART3: SlopeHeights = [
+        11, 12, 12, 12, 13, 13, 13, 14, 14, 14, 15, 15, 15, 16, 16, 16,
     ];
+    pub const SLOPE_STEEP_HILL_PART1: SlopeHeights =
+        [0, 0, 0, 0, 0, 0, 0, 0, 2, 4, 6, 8, 10, 12, 14, 16];
+    pub const SLOPE_STEEP_HILL_PART2: SlopeHeights =
+        [2, 4, 6, 8, 10, 12, 14, 16, 16, 16, 16, 16, 16, 16, 16, 16];
+    pub const SLOPE_STEEPER_HILL_PART1: SlopeHeights =
+        [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 4, 7, 10, 13, 16];
+    pub const SLOPE_STEEPER_HILL_PART2: SlopeHeights =
+        [0, 0, 0, 0, 0, 2, 5, 8, 11, 14, 16, 16, 16, 16, 16, 16];
+    pub const SLOPE_STEEPER_HILL_PART3: SlopeHeights =
+        [3, 6, 9, 12, 15, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16];
+
+    /// The polygons of the slope types that aren't a height per column.
+    pub fn polygons(slope: SlopeType) -> Option<SlopePolygons> {
+        match slope {
+            SlopeType::HorizontalLines => Some(SLOPE_HORIZONTAL_LINES),
+            _ => None,
+        }
+    }
+
+    /// The height table of a slope type, `None` for `SlopeType::None` and
+    /// the types in `polygons`.
+    pub fn heights(slope: SlopeType) -> Option<&'static SlopeHeights> {
+        match slope {
+            SlopeType::HalfSolidH => Some(&SLOPE_HALF_SOLIDH),
+            SlopeType::HalfSolidV => Some(&SLOPE_HALF_SOLIDV),
+            SlopeType::QuarterSolid => Some(&SLOPE_QUARTER_SOLID),
+            SlopeType::StairBigSteps => Some(&SLOPE_STAIR_BIG_STEPS),
+            SlopeType::FullSolidUnused => Some(&SLOPE_FULL_SOLID_UNUSED),
+            SlopeType::SmallTriangle => Some(&SLOPE_SMALL_TRIANGLE),
+            SlopeType::BigTriangle => Some(&SLOPE_BIG_TRIANGLE),
+            SlopeType::HalfPlat => Some(&SLOPE_HALF_PLAT),
+            SlopeType::SquareDuplicate1 => Some(&SLOPE_SQUARE_DUPLICATE),
+            SlopeType::SquareDuplicate2 => Some(&SLOPE_SQUARE_DUPLICATE),
+            SlopeType::SquareDuplicate3 => Some(&SLOPE_SQUARE_DUPLICATE),
+            SlopeType::SquareDuplicate4 => Some(&SLOPE_SQUARE_DUPLICATE),
+            SlopeType::SquareDuplicate5 => Some(&SLOPE_SQUARE_DUPLICATE),
+            SlopeType::SquareDuplicate6 => Some(&SLOPE_SQUARE_DUPLICATE),
+            SlopeType::StairSmallSteps => Some(&SLOPE_STAIR_SMALL_STEPS),
+            SlopeType::ConcaveTriangle => Some(&SLOPE_CONCAVE_TRIANGLE),
+            SlopeType::HorizontalLines => None,
+            SlopeType::VerticalLines => Some(&SLOPE_VERTICAL_LINES),
+            SlopeType::Slope45 => Some(&SLOPE_45),
+            SlopeType::Square => Some(&SLOPE_SQUARE),
+            SlopeType::HillPart1 => Some(&SLOPE_HILL_PART1),
+            SlopeType::HillPart2 => Some(&SLOPE_HILL_PART2),
+            SlopeType::SmoothHillPart1 => Some(&SLOPE_SMOOTH_HILL_PART1),
+            SlopeType::SmoothHillPart2 => Some(&SLOPE_SMOOTH_HILL_PART2),
+            SlopeType::SmootherHillPart1 => Some(&SLOPE_SMOOTHER_HILL_PART1),
+            SlopeType::SmootherHillPart2 => Some(&SLOPE_SMOOTHER_HILL_PART2),
+            SlopeType::SmootherHillPart3 => Some(&SLOPE_SMOOTHER_HILL_PART3),
+            SlopeType::SteepHillPart1 => Some(&SLOPE_STEEP_HILL_PART1),
+            SlopeType::SteepHillPart2 => Some(&SLOPE_STEEP_HILL_PART2),
+            SlopeType::SteeperHillPart1 => Some(&SLOPE_STEEPER_HILL_PART1),
+            SlopeType::SteeperHillPart2 => Some(&SLOPE_STEEPER_HILL_PART2),
+            SlopeType::SteeperHillPart3 => Some(&SLOPE_STEEPER_HILL_PART3),
+            SlopeType::None => None,
+        }
+    }
 }
 
 pub mod vectors {
     use imageproc::point::Point;
 
+    use super::{babel, column_runs};
     use crate::{constants::CELL_SIZE, types::SlopeType};
 
     #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -444,155 +543,336 @@ pub mod vectors {
         }
     }
 
-    pub const VEC_SLOPE_HALF_SOLIDH: [Vector; 1] = [Vector {
-        start: Point {
-            x: 0,
-            y: CELL_SIZE as i32 / 2,
-        },
-        end: Point {
-            x: CELL_SIZE as i32,
-            y: CELL_SIZE as i32 / 2,
-        },
-    }];
+    /// The surface lines of a slope cell, used for collision.
+    pub type SlopeVectors = Vec<Vector>;
 
-    pub const VEC_SLOPE_HALF_SOLIDV: [Vector; 1] = [Vector {
-        start: Point {
-            x: CELL_SIZE as i32 / 2,
-            y: 0,
-        },
-        end: Point {
-            x: CELL_SIZE as i32 / 2,
-            y: CELL_SIZE as i32,
-        },
-    }];
+    impl From<SlopeType> for SlopeVectors {
+        fn from(slope: SlopeType) -> Vec<Vector> {
+            if let Some(polygons) = babel::polygons(slope) {
+                return polygons
+                    .iter()
+                    .flat_map(|points| {
+                        let mut ring = points.to_vec();
+                        ring.push(points[0]);
+                        edge_vectors(&ring)
+                    })
+                    .collect();
+            }
+            match babel::heights(slope) {
+                Some(heights) => vectors_from_heights(heights),
+                None => Vec::new(),
+            }
+        }
+    }
 
-    pub const VEC_SLOPE_SMALL_TRIANGLE: [Vector; 2] = [
-        Vector {
-            start: Point {
-                x: 0,
-                y: CELL_SIZE as i32,
-            },
-            end: Point {
-                x: CELL_SIZE as i32 / 2,
-                y: CELL_SIZE as i32 / 2,
-            },
-        },
-        Vector {
-            start: Point {
-                x: CELL_SIZE as i32,
-                y: CELL_SIZE as i32,
-            },
-            end: Point {
-                x: CELL_SIZE as i32 / 2,
-                y: CELL_SIZE as i32 / 2,
-            },
-        },
-    ];
+    /// Builds the surface lines of a slope definition.
+    ///
+    /// The outline through the top of every column is straightened as long as
+    /// no column top is more than a pixel away from the line, then every edge
+    /// not running along the cell border becomes a vector. Coordinates on the
+    /// last pixel are widened to the cell edge at `CELL_SIZE`.
+    pub fn vectors_from_heights(heights: &babel::SlopeHeights) -> Vec<Vector> {
+        let last = CELL_SIZE as i32 - 1;
+        let top = |column: usize| CELL_SIZE as i32 - heights[column] as i32;
+
+        let mut vectors = Vec::new();
+        for (start, end) in column_runs(heights) {
+            let surface: Vec<Point<i32>> = (start..=end)
+                .map(|column| Point::new(column as i32, top(column)))
+                .collect();
+
+            let mut points = vec![Point::new(start as i32, last)];
+            points.extend(straighten(&surface));
+            points.push(Point::new(end as i32, last));
+
+            vectors.extend(edge_vectors(&points));
+        }
 
-    pub const VEC_SLOPE_BIG_TRIANGLE: [Vector; 2] = [
-        Vector {
-            start: Point {
-                x: 0,
-                y: CELL_SIZE as i32,
-            },
-            end: Point {
-                x: CELL_SIZE as i32 / 2,
-                y: 0,
-            },
-        },
-        Vector {
-            start: Point {
-                x: CELL_SIZE as i32,
-                y: CELL_SIZE as i32,
+        vectors
+    }
+
+    // every edge of the outline not running along the cell border, widened to the cell edge
+    fn edge_vectors(points: &[Point<i32>]) -> Vec<Vector> {
+        let last = CELL_SIZE as i32 - 1;
+        let widen = |point: Point<i32>| Point {
+            x: if point.x == last {
+                CELL_SIZE as i32
+            } else {
+                point.x
             },
-            end: Point {
-                x: CELL_SIZE as i32 / 2,
-                y: 0,
+            y: if point.y == last {
+                CELL_SIZE as i32
+            } else {
+                point.y
             },
-        },
-    ];
+        };
+        let on_border = |start: Point<i32>, end: Point<i32>| {
+            (start.x == end.x && (start.x == 0 || start.x == last))
+                || (start.y == end.y && (start.y == 0 || start.y == last))
+        };
+
+        points
+            .windows(2)
+            .filter(|pair| pair[0] != pair[1] && !on_border(pair[0], pair[1]))
+            .map(|pair| Vector::new(widen(pair[0]), widen(pair[1])))
+            .collect()
+    }
 
-    pub const VEC_SLOPE45: [Vector; 1] = [Vector {
-        start: Point {
-            x: 0,
-            y: CELL_SIZE as i32,
-        },
-        end: Point {
-            x: CELL_SIZE as i32,
-            y: 0,
-        },
-    }];
+    // greedily merge segments while every skipped point stays within a pixel of the line
+    fn straighten(points: &[Point<i32>]) -> Vec<Point<i32>> {
+        let mut result = vec![points[0]];
+        let mut anchor = 0;
+
+        while anchor < points.len() - 1 {
+            let mut next = anchor + 1;
+            while next + 1 < points.len()
+                && (anchor + 1..next + 1)
+                    .all(|i| distance_to_line(points[i], points[anchor], points[next + 1]) <= 1.0)
+            {
+                next += 1;
+            }
+            result.push(points[next]);
+            anchor = next;
+        }
 
-    pub const VEC_SLOPE_HILL_PART1: [Vector; 1] = [Vector {
-        start: Point {
-            x: CELL_SIZE as i32 / 2,
-            y: CELL_SIZE as i32,
-        },
-        end: Point {
-            x: CELL_SIZE as i32,
-            y: CELL_SIZE as i32 / 2,
-        },
-    }];
+        result
+    }
 
-    pub const VEC_SLOPE_HILL_PART2: [Vector; 1] = [Vector {
-        start: Point {
-            x: 0,
-            y: CELL_SIZE as i32 / 2,
-        },
-        end: Point {
-            x: CELL_SIZE as i32 / 2,
-            y: 0,
-        },
-    }];
+    fn distance_to_line(point: Point<i32>, start: Point<i32>, end: Point<i32>) -> f64 {
+        let line = Vector::new(start, end);
+        let length = line.length();
+        if length == 0.0 {
+            return Vector::new(start, point).length();
+        }
+        let cross =
+            (end.x - start.x) * (point.y - start.y) - (end.y - start.y) * (point.x - start.x);
+        cross.abs() as f64 / length
+    }
+}
 
-    pub type SlopeVectors = &'static [Vector];
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_slope_type_has_a_shape() {
+        for value in 0x00..=0x1F {
+            let slope_type = SlopeType::from(value);
+            let shape = SlopeShape::from(slope_type);
+
+            assert!(!shape.is_empty(), "{:?} has no shape", slope_type);
+            for polygon in &shape {
+                assert!(polygon.points.len() >= 3, "{:?} is degenerate", slope_type);
+                assert!(
+                    polygon
+                        .points
+                        .iter()
+                        .all(|point| (0..CELL_SIZE as i32).contains(&point.x)
+                            && (0..CELL_SIZE as i32).contains(&point.y)),
+                    "{:?} leaves the cell",
+                    slope_type
+                );
+            }
+        }
+    }
 
-    impl From<SlopeType> for SlopeVectors {
-        fn from(slope: SlopeType) -> &'static [Vector] {
-            match slope {
-                SlopeType::HalfSolidH => &VEC_SLOPE_HALF_SOLIDH,
-                SlopeType::HalfSolidV => &VEC_SLOPE_HALF_SOLIDV,
-                SlopeType::SmallTriangle => &VEC_SLOPE_SMALL_TRIANGLE,
-                SlopeType::BigTriangle => &VEC_SLOPE_BIG_TRIANGLE,
-                SlopeType::Slope45 => &VEC_SLOPE45,
-                SlopeType::HillPart1 => &VEC_SLOPE_HILL_PART1,
-                SlopeType::HillPart2 => &VEC_SLOPE_HILL_PART2,
-                // _ => &VEC_SLOPE_TEST,
-                // _ => &VEC_SLOPE_TEST3,
-                _ => &[],
+    #[test]
+    fn shapes_cover_exactly_the_slope_definition() {
+        for value in 0x00..=0x1F {
+            let slope_type = SlopeType::from(value);
+            let Some(heights) = babel::heights(slope_type) else {
+                continue;
+            };
+
+            let mut img = image::GrayImage::new(CELL_SIZE as u32, CELL_SIZE as u32);
+            for polygon in SlopeShape::from(slope_type) {
+                imageproc::drawing::draw_polygon_mut(&mut img, &polygon.points, image::Luma([255]));
+            }
+
+            for (x, height) in heights.iter().enumerate() {
+                let filled = (0..CELL_SIZE as u32)
+                    .filter(|y| img.get_pixel(x as u32, *y)[0] != 0)
+                    .count();
+                assert_eq!(filled, *height as usize, "{:?} column {}", slope_type, x);
+            }
+        }
+    }
+
+    #[test]
+    fn horizontal_lines_are_two_strips() {
+        let shape = SlopeShape::from(SlopeType::HorizontalLines);
+        assert_eq!(shape.len(), 2);
+
+        let img = rasterize(&shape);
+        for y in 0..CELL_SIZE as u32 {
+            let solid = (4..8).contains(&y) || (12..16).contains(&y);
+            for x in 0..CELL_SIZE as u32 {
+                assert_eq!(img.get_pixel(x, y)[0] != 0, solid, "{}, {}", x, y);
             }
         }
+
+        let vectors = vectors::SlopeVectors::from(SlopeType::HorizontalLines);
+        assert_eq!(
+            vectors,
+            [
+                vectors::Vector::new(Point::new(0, 4), Point::new(16, 4)),
+                vectors::Vector::new(Point::new(16, 7), Point::new(0, 7)),
+                vectors::Vector::new(Point::new(0, 12), Point::new(16, 12)),
+            ]
+        );
+    }
+
+    #[test]
+    fn smooth_hill_parts_continue_each_other() {
+        let hill: Vec<u8> = babel::SLOPE_SMOOTH_HILL_PART1
+            .iter()
+            .chain(&babel::SLOPE_SMOOTH_HILL_PART2)
+            .copied()
+            .collect();
+        assert_eq!(
+            (
+                babel::SLOPE_SMOOTH_HILL_PART1[15],
+                babel::SLOPE_SMOOTH_HILL_PART2[0]
+            ),
+            (8, 9)
+        );
+        for (x, height) in hill.iter().enumerate() {
+            assert_eq!(*height as usize, (x + 2) / 2, "column {}", x);
+        }
+    }
+
+    #[test]
+    fn half_plat_collides_like_half_solid() {
+        assert_eq!(
+            SlopeShape::from(SlopeType::HalfPlat),
+            SlopeShape::from(SlopeType::HalfSolidH)
+        );
     }
 
-    // pub const VEC_SLOPE_TEST: [Vector; 1] = [Vector {
-    //     start: Point { x: 0, y: 0 },
-    //     end: Point {
-    //         x: CELL_SIZE as i32,
-    //         y: CELL_SIZE as i32,
-    //     },
-    // }];
-
-    // pub const VEC_SLOPE_TEST2: [Vector; 1] = [Vector {
-    //     start: Point { x: 0, y: 0 },
-    //     end: Point {
-    //         x: 0,
-    //         y: CELL_SIZE as i32,
-    //     },
-    // }];
-
-    // pub const VEC_SLOPE_TEST3: [Vector; 1] = [Vector {
-    //     start: Point { x: 0, y: 0 },
-    //     end: Point {
-    //         x: CELL_SIZE as i32,
-    //         y: 0,
-    //     },
-    // }];
-
-    // pub const VEC_SLOPE_TEST4: [Vector; 1] = [Vector {
-    //     start: Point {
-    //         x: CELL_SIZE as i32,
-    //         y: 0,
-    //     },
-    //     end: Point { x: 0, y: 0 },
-    // }];
+    fn rasterize(shape: &[Polygon]) -> image::GrayImage {
+        let mut img = image::GrayImage::new(CELL_SIZE as u32, CELL_SIZE as u32);
+        for polygon in shape {
+            imageproc::drawing::draw_polygon_mut(&mut img, &polygon.points, image::Luma([255]));
+        }
+        img
+    }
+
+    #[test]
+    fn widen_moves_points_onto_the_pixel_edges() {
+        // a 16x4 strip with a one pixel wide spike on top, clockwise on screen
+        let mut polygon = Polygon::new(vec![
+            Point::new(0, 12),
+            Point::new(4, 12),
+            Point::new(4, 0),
+            Point::new(4, 12),
+            Point::new(15, 12),
+            Point::new(15, 15),
+            Point::new(0, 15),
+        ]);
+        polygon.widen();
+        assert_eq!(
+            polygon.points,
+            [
+                Point::new(0, 12),
+                Point::new(4, 12),
+                Point::new(4, 0),
+                Point::new(5, 0),
+                Point::new(5, 12),
+                Point::new(16, 12),
+                Point::new(16, 16),
+                Point::new(0, 16),
+            ]
+        );
+    }
+
+    #[test]
+    fn symplify_removes_repeated_and_collinear_points() {
+        let mut polygon = Polygon::new(vec![
+            Point::new(0, 0),
+            Point::new(0, 0),
+            Point::new(4, 0),
+            Point::new(8, 0),
+            Point::new(8, 8),
+            Point::new(0, 8),
+            Point::new(0, 4),
+            Point::new(0, 0),
+        ]);
+        polygon.symplify();
+
+        assert_eq!(
+            polygon.points,
+            vec![
+                Point::new(8, 0),
+                Point::new(8, 8),
+                Point::new(0, 8),
+                Point::new(0, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn symplify_keeps_every_babel_shape_intact() {
+        for value in 0x00..=0x1F {
+            let slope_type = SlopeType::from(value);
+            let Some(heights) = babel::heights(slope_type) else {
+                continue;
+            };
+
+            // rebuild every column top without any simplification
+            let mut raw = shape_from_heights(heights);
+            for polygon in raw.iter_mut() {
+                let mut points = Vec::new();
+                for pair in polygon.points.windows(2) {
+                    let (a, b) = (pair[0], pair[1]);
+                    points.push(a);
+                    points.push(a);
+                    if (b.x - a.x).abs() > 1 && a.y == b.y {
+                        points.push(Point::new((a.x + b.x) / 2, a.y));
+                    }
+                }
+                points.push(*polygon.points.last().unwrap());
+                polygon.points = points;
+            }
+
+            let expected = rasterize(&raw);
+            for polygon in raw.iter_mut() {
+                polygon.symplify();
+
+                let len = polygon.points.len();
+                for i in 0..len {
+                    assert_ne!(polygon.points[i], polygon.points[(i + 1) % len]);
+                }
+                let before = polygon.clone();
+                polygon.symplify();
+                assert_eq!(*polygon, before, "{:?} is not stable", slope_type);
+            }
+
+            assert_eq!(rasterize(&raw), expected, "{:?} changed shape", slope_type);
+            assert_eq!(raw, shape_from_heights(heights), "{:?}", slope_type);
+        }
+    }
+
+    #[test]
+    fn symplify_with_tolerance_straightens_babel_slopes() {
+        for value in 0x00..=0x1F {
+            let slope_type = SlopeType::from(value);
+            for polygon in SlopeShape::from(slope_type) {
+                let mut simplified = polygon.clone();
+                simplified.symplify_with_tolerance(1.0);
+
+                assert!(simplified.points.len() >= 3, "{:?}", slope_type);
+                assert!(simplified.points.len() <= polygon.points.len());
+                assert!(
+                    (simplified.signed_area() - polygon.signed_area()).abs()
+                        <= CELL_SIZE as f32 * 2.0,
+                    "{:?} moved too far",
+                    slope_type
+                );
+            }
+        }
+
+        let mut hill = SlopeShape::from(SlopeType::SmoothHillPart1).remove(0);
+        hill.symplify_with_tolerance(1.0);
+        assert_eq!(hill.points.len(), 3);
+    }
 }
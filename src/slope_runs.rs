@@ -0,0 +1,240 @@
+use imageproc::point::Point;
+
+use crate::{
+    shapes::vectors::Vector,
+    types::{BlockType, Cell, Flip},
+};
+
+/// A continuous slope made of several adjacent slope cells, e.g. a chain of
+/// `Slope45` cells or `HillPart1` followed by `HillPart2`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlopeRun {
+    /// Indices into `Room::cells`, from left to right.
+    pub cells: Vec<usize>,
+    /// Leftmost point of the slope surface, in room pixels.
+    pub start: Point<i32>,
+    /// Rightmost point of the slope surface, in room pixels.
+    pub end: Point<i32>,
+    /// Whether the solid side is above the surface.
+    pub ceiling: bool,
+}
+
+impl SlopeRun {
+    /// Rise over run of the whole slope, positive when it goes up to the right.
+    pub fn gradient(&self) -> f32 {
+        let dx = self.end.x - self.start.x;
+        if dx == 0 {
+            return 0.0;
+        }
+        (self.start.y - self.end.y) as f32 / dx as f32
+    }
+
+    pub fn length(&self) -> f64 {
+        Vector::new(self.start, self.end).length()
+    }
+}
+
+// the diagonal surface lines of a slope cell, in room pixels and from left to right
+fn diagonal_vectors(cell: &Cell) -> Vec<Vector> {
+    if cell.effective_block_type() != BlockType::Slope {
+        return Vec::new();
+    }
+
+    cell.surface_vectors()
+        .into_iter()
+        .filter(|vector| vector.start.x != vector.end.x && vector.start.y != vector.end.y)
+        .map(|vector| {
+            if vector.start.x > vector.end.x {
+                Vector::new(vector.end, vector.start)
+            } else {
+                vector
+            }
+        })
+        .collect()
+}
+
+fn is_ceiling(cell: &Cell) -> bool {
+    matches!(cell.get_slope_flip(), Flip::Vertical | Flip::Both)
+}
+
+// two surfaces continue each other when one ends within a pixel of where the
+// other starts and both go in the same vertical direction
+fn continues(left: &Vector, right: &Vector) -> bool {
+    (left.end.x - right.start.x).abs() <= 1
+        && (left.end.y - right.start.y).abs() <= 1
+        && (left.end.y - left.start.y).signum() == (right.end.y - right.start.y).signum()
+}
+
+fn find(sets: &mut [usize], index: usize) -> usize {
+    let mut root = index;
+    while sets[root] != root {
+        root = sets[root];
+    }
+
+    // path compression
+    let mut current = index;
+    while sets[current] != root {
+        let next = sets[current];
+        sets[current] = root;
+        current = next;
+    }
+
+    root
+}
+
+fn union(sets: &mut [usize], a: usize, b: usize) {
+    let a = find(sets, a);
+    let b = find(sets, b);
+    if a != b {
+        sets[a.max(b)] = a.min(b);
+    }
+}
+
+/// Groups adjacent slope cells into runs with a union-find over the cells.
+///
+/// Returns the representative cell of every cell, which is the cell itself for
+/// anything that isn't part of a slope, and the runs in order of their representative.
+pub fn find_slope_runs(cells: &[Cell], room_width: usize) -> (Vec<usize>, Vec<SlopeRun>) {
+    let mut sets: Vec<usize> = (0..cells.len()).collect();
+    let vectors: Vec<Vec<Vector>> = cells.iter().map(diagonal_vectors).collect();
+
+    for (i, cell) in cells.iter().enumerate() {
+        if vectors[i].is_empty() {
+            continue;
+        }
+
+        // the surface can only continue into the cell above or below, for
+        // steep hills, or into the right, up-right or down-right cell
+        let mut neighbors = Vec::new();
+        if i >= room_width {
+            neighbors.push(i - room_width);
+        }
+        if i + room_width < cells.len() {
+            neighbors.push(i + room_width);
+        }
+        if i % room_width + 1 < room_width {
+            neighbors.push(i + 1);
+            if i >= room_width {
+                neighbors.push(i + 1 - room_width);
+            }
+            if i + 1 + room_width < cells.len() {
+                neighbors.push(i + 1 + room_width);
+            }
+        }
+
+        for neighbor in neighbors {
+            if vectors[neighbor].is_empty() || is_ceiling(cell) != is_ceiling(&cells[neighbor]) {
+                continue;
+            }
+
+            let joined = vectors[i]
+                .iter()
+                .any(|left| vectors[neighbor].iter().any(|right| continues(left, right)));
+            if joined {
+                union(&mut sets, i, neighbor);
+            }
+        }
+    }
+
+    for i in 0..sets.len() {
+        find(&mut sets, i);
+    }
+
+    let mut runs: Vec<SlopeRun> = Vec::new();
+    for (i, cell) in cells.iter().enumerate() {
+        if vectors[i].is_empty() {
+            continue;
+        }
+
+        // runs are created in order of their representative, the lowest index in the set
+        let root = sets[i];
+        let run = match runs.iter_mut().find(|run| sets[run.cells[0]] == root) {
+            Some(run) => run,
+            None => {
+                let first = vectors[i][0];
+                runs.push(SlopeRun {
+                    cells: Vec::new(),
+                    start: first.start,
+                    end: first.end,
+                    ceiling: is_ceiling(cell),
+                });
+                runs.last_mut().unwrap()
+            }
+        };
+
+        run.cells.push(i);
+        for vector in &vectors[i] {
+            if vector.start.x < run.start.x {
+                run.start = vector.start;
+            }
+            if vector.end.x > run.end.x {
+                run.end = vector.end;
+            }
+        }
+    }
+
+    for run in runs.iter_mut() {
+        run.cells
+            .sort_by_key(|index| (index % room_width, index / room_width));
+    }
+
+    (sets, runs)
+}
+
+#[cfg(test)]
+mod tests {
+    use imageproc::point::Point;
+
+    use crate::types::room_from;
+
+    #[test]
+    fn slope_45_chain_is_one_run() {
+        let room = room_from(|x, y| match (x, y) {
+            (2, 10) | (3, 9) | (4, 8) => (0x1000, 0x12),
+            (2..=4, 11) => (0x8000, 0),
+            _ => (0, 0),
+        });
+
+        assert_eq!(room.slope_runs.len(), 1);
+        let run = &room.slope_runs[0];
+        assert_eq!(run.cells, vec![2 + 10 * 16, 3 + 9 * 16, 4 + 8 * 16]);
+        assert!(!run.ceiling);
+        assert!((run.gradient() - 1.0).abs() < 0.1);
+        let root = room.slope_union_sets[run.cells[0]];
+        assert!(run.cells.iter().all(|&i| room.slope_union_sets[i] == root));
+    }
+
+    #[test]
+    fn hill_parts_join_and_separate_slopes_stay_apart() {
+        let room = room_from(|x, y| match (x, y) {
+            (5, 5) => (0x1000, 0x14),
+            (6, 5) => (0x1000, 0x15),
+            (10, 5) => (0x1000, 0x12),
+            _ => (0, 0),
+        });
+
+        assert_eq!(room.slope_runs.len(), 2);
+        assert_eq!(room.slope_runs[0].cells, vec![5 + 5 * 16, 6 + 5 * 16]);
+        assert!(room.slope_runs[0].gradient() > 0.0);
+        assert_eq!(room.slope_runs[1].cells, vec![10 + 5 * 16]);
+    }
+
+    #[test]
+    fn stacked_steep_hill_is_one_run() {
+        // the steeper hill climbs straight up from one cell into the one above
+        let room = room_from(|x, y| match (x, y) {
+            (5, 9) => (0x1000, 0x1D),
+            (5, 10) => (0x1000, 0x1E),
+            (5, 11) => (0x8000, 0),
+            _ => (0, 0),
+        });
+
+        assert_eq!(room.slope_runs.len(), 1);
+        let run = &room.slope_runs[0];
+        assert_eq!(run.cells, vec![5 + 9 * 16, 5 + 10 * 16]);
+        assert_eq!(
+            (run.start, run.end),
+            (Point::new(85, 174), Point::new(96, 144))
+        );
+    }
+}
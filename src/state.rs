@@ -0,0 +1,419 @@
+//! Room states and the objects placed in them, as stored in the ROM.
+//!
+//! A room header is followed by a list of states, each with a condition that
+//! selects it and pointers to the level data, PLMs, enemies and FX used while
+//! it is active. The last state in the list is the default one. `Rom` reads
+//! these into the types here, see `Room::states` and `Room::doors`.
+
+use std::{fmt, str::FromStr};
+
+/// Size of the data of one state.
+pub const STATE_SIZE: usize = 26;
+/// Size of one entry of a door list, in `DOOR_BANK`.
+pub const DOOR_SIZE: usize = 12;
+/// Size of one PLM of a PLM set.
+pub const PLM_SIZE: usize = 6;
+/// Size of one enemy of an enemy population.
+pub const ENEMY_SIZE: usize = 16;
+/// Size of one FX entry.
+pub const FX_SIZE: usize = 16;
+
+/// Bank of the PLM sets.
+pub const PLM_BANK: u8 = 0x8F;
+/// Bank of the enemy populations.
+pub const ENEMY_POPULATION_BANK: u8 = 0xA1;
+/// Bank of the enemy sets, the graphics loaded for the enemies.
+pub const ENEMY_SET_BANK: u8 = 0xB4;
+/// Bank of the FX entries.
+pub const FX_BANK: u8 = 0x83;
+
+fn word(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+/// What selects a state when the room is entered.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StateCondition {
+    /// Used when no other condition applies, always the last state.
+    Default,
+    /// The room was entered through the door at this pointer into `DOOR_BANK`.
+    Door(u16),
+    /// The boss of the area is dead.
+    MainAreaBossDead,
+    /// The event with this index is set.
+    Event(u8),
+    /// The bosses in this bitset are dead.
+    BossDead(u8),
+    /// Samus has the morph ball.
+    MorphBall,
+    /// Samus has the morph ball and missiles.
+    MorphBallAndMissiles,
+    /// Samus has power bombs.
+    PowerBombs,
+    /// Samus has the speed booster.
+    SpeedBooster,
+}
+
+impl StateCondition {
+    pub const DEFAULT_CODE: u16 = 0xE5E6;
+
+    /// The pointer to the game's routine testing the condition, which is how
+    /// the state list stores it.
+    pub fn code(&self) -> u16 {
+        match self {
+            StateCondition::Default => Self::DEFAULT_CODE,
+            StateCondition::Door(_) => 0xE5EB,
+            StateCondition::MainAreaBossDead => 0xE5FF,
+            StateCondition::Event(_) => 0xE612,
+            StateCondition::BossDead(_) => 0xE629,
+            StateCondition::MorphBall => 0xE640,
+            StateCondition::MorphBallAndMissiles => 0xE652,
+            StateCondition::PowerBombs => 0xE669,
+            StateCondition::SpeedBooster => 0xE678,
+        }
+    }
+
+    /// Number of parameter bytes following `code` in the state list, `None`
+    /// for an unknown code.
+    pub fn parameter_size(code: u16) -> Option<usize> {
+        match code {
+            0xE5E6 | 0xE5FF | 0xE640 | 0xE652 | 0xE669 | 0xE678 => Some(0),
+            0xE612 | 0xE629 => Some(1),
+            0xE5EB => Some(2),
+            _ => None,
+        }
+    }
+
+    /// The condition for `code`, with its parameter bytes.
+    pub fn from_code(code: u16, parameter: &[u8]) -> Option<Self> {
+        if parameter.len() < Self::parameter_size(code)? {
+            return None;
+        }
+
+        Some(match code {
+            0xE5E6 => StateCondition::Default,
+            0xE5EB => StateCondition::Door(word(parameter, 0)),
+            0xE5FF => StateCondition::MainAreaBossDead,
+            0xE612 => StateCondition::Event(parameter[0]),
+            0xE629 => StateCondition::BossDead(parameter[0]),
+            0xE640 => StateCondition::MorphBall,
+            0xE652 => StateCondition::MorphBallAndMissiles,
+            0xE669 => StateCondition::PowerBombs,
+            _ => StateCondition::SpeedBooster,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            StateCondition::Default => "default",
+            StateCondition::Door(_) => "door",
+            StateCondition::MainAreaBossDead => "main_area_boss_dead",
+            StateCondition::Event(_) => "event",
+            StateCondition::BossDead(_) => "boss_dead",
+            StateCondition::MorphBall => "morph_ball",
+            StateCondition::MorphBallAndMissiles => "morph_ball_and_missiles",
+            StateCondition::PowerBombs => "power_bombs",
+            StateCondition::SpeedBooster => "speed_booster",
+        }
+    }
+}
+
+impl fmt::Display for StateCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateCondition::Door(door) => write!(f, "{} ${:04X}", self.name(), door),
+            StateCondition::Event(value) | StateCondition::BossDead(value) => {
+                write!(f, "{} {:#04X}", self.name(), value)
+            }
+            _ => write!(f, "{}", self.name()),
+        }
+    }
+}
+
+/// A door leading out of a room.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Door {
+    /// Address of the door data.
+    pub address: u32,
+    /// Pointer to the destination room header in `ROOM_BANK`, 0 for the
+    /// elevator doors leading nowhere.
+    pub destination: u16,
+    pub bitflag: u8,
+    pub direction: u8,
+    /// Position of the door cap in the destination room, in tiles.
+    pub cap_x: u8,
+    pub cap_y: u8,
+    /// Screen of the destination room the door leads to.
+    pub screen_x: u8,
+    pub screen_y: u8,
+    pub spawn_distance: u16,
+    pub asm: u16,
+}
+
+impl Door {
+    /// Parses the `DOOR_SIZE` bytes of the door at `address`.
+    pub fn from_bytes(address: u32, bytes: &[u8]) -> Self {
+        Door {
+            address,
+            destination: word(bytes, 0x0),
+            bitflag: bytes[0x2],
+            direction: bytes[0x3],
+            cap_x: bytes[0x4],
+            cap_y: bytes[0x5],
+            screen_x: bytes[0x6],
+            screen_y: bytes[0x7],
+            spawn_distance: word(bytes, 0x8),
+            asm: word(bytes, 0xA),
+        }
+    }
+}
+
+/// A post-load modification: items, gates, shot blocks, scroll changers and
+/// other objects placed on the level.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Plm {
+    pub id: u16,
+    /// Position in tiles.
+    pub x: u8,
+    pub y: u8,
+    pub parameter: u16,
+}
+
+impl Plm {
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Plm {
+            id: word(bytes, 0x0),
+            x: bytes[0x2],
+            y: bytes[0x3],
+            parameter: word(bytes, 0x4),
+        }
+    }
+
+    pub fn category(&self) -> PlmCategory {
+        match self.id {
+            ITEMS_START..=ITEMS_END => PlmCategory::Item,
+            0xC842..=0xC8CA => PlmCategory::Door,
+            0xC826..=0xC83E => PlmCategory::Gate,
+            0xB63B..=0xB70F => PlmCategory::Scroll,
+            _ => PlmCategory::Other,
+        }
+    }
+
+    /// Short name of the item for item PLMs, the same for visible, chozo orb
+    /// and hidden items.
+    pub fn item_name(&self) -> Option<&'static str> {
+        if !(ITEMS_START..=ITEMS_END).contains(&self.id) {
+            return None;
+        }
+
+        // every kind of item comes in the same order, one id every 4 bytes
+        let index = (self.id - ITEMS_START) as usize / 4 % ITEM_NAMES.len();
+        Some(ITEM_NAMES[index])
+    }
+
+    /// The item name, or the id in hex for every other PLM.
+    pub fn label(&self) -> String {
+        match self.item_name() {
+            Some(name) => name.to_string(),
+            None => format!("{:04X}", self.id),
+        }
+    }
+}
+
+// visible items, then chozo orb items, then items hidden in a shot block
+const ITEMS_START: u16 = 0xEED7;
+const ITEMS_END: u16 = 0xEFCF;
+const ITEM_NAMES: [&str; 21] = [
+    "ETANK", "MISSILE", "SUPER", "PB", "BOMBS", "CHARGE", "ICE", "HIJUMP", "SPEED", "WAVE",
+    "SPAZER", "SPRING", "VARIA", "GRAVITY", "XRAY", "PLASMA", "GRAPPLE", "SPACE", "SCREW", "MORPH",
+    "RESERVE",
+];
+
+/// The kinds of PLMs the collision overlay tells apart.
+///
+/// The id ranges are those of the vanilla game, PLMs added by hacks are `Other`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PlmCategory {
+    /// Visible, chozo orb and hidden items.
+    Item,
+    /// Door caps, including grey doors.
+    Door,
+    /// Gates and their shot blocks.
+    Gate,
+    /// Scroll changers.
+    Scroll,
+    Other,
+}
+
+impl PlmCategory {
+    pub const ALL: [PlmCategory; 5] = [
+        PlmCategory::Item,
+        PlmCategory::Door,
+        PlmCategory::Gate,
+        PlmCategory::Scroll,
+        PlmCategory::Other,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PlmCategory::Item => "item",
+            PlmCategory::Door => "door",
+            PlmCategory::Gate => "gate",
+            PlmCategory::Scroll => "scroll",
+            PlmCategory::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for PlmCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for PlmCategory {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.to_lowercase();
+        PlmCategory::ALL
+            .into_iter()
+            .find(|category| category.name() == name)
+            .ok_or_else(|| format!("unknown plm category: {}", s))
+    }
+}
+
+/// An enemy of an enemy population.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Enemy {
+    /// Pointer to the enemy species header in bank $A0.
+    pub id: u16,
+    /// Position in pixels.
+    pub x: u16,
+    pub y: u16,
+    pub initial_parameter: u16,
+    pub properties: u16,
+    pub extra_properties: u16,
+    pub parameter1: u16,
+    pub parameter2: u16,
+}
+
+impl Enemy {
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Enemy {
+            id: word(bytes, 0x0),
+            x: word(bytes, 0x2),
+            y: word(bytes, 0x4),
+            initial_parameter: word(bytes, 0x6),
+            properties: word(bytes, 0x8),
+            extra_properties: word(bytes, 0xA),
+            parameter1: word(bytes, 0xC),
+            parameter2: word(bytes, 0xE),
+        }
+    }
+}
+
+/// Liquids, fog, lighting and similar effects of a state.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Fx {
+    /// The door this entry applies to, 0 for every door.
+    pub door: u16,
+    pub base_y: u16,
+    pub target_y: u16,
+    pub y_velocity: u16,
+    pub timer: u8,
+    pub fx_type: u8,
+    pub default_layer_blending: u8,
+    pub layer3_layer_blending: u8,
+    pub liquid_options: u8,
+    pub palette_fx: u8,
+    pub animated_tiles: u8,
+    pub palette_blend: u8,
+}
+
+impl Fx {
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Fx {
+            door: word(bytes, 0x0),
+            base_y: word(bytes, 0x2),
+            target_y: word(bytes, 0x4),
+            y_velocity: word(bytes, 0x6),
+            timer: bytes[0x8],
+            fx_type: bytes[0x9],
+            default_layer_blending: bytes[0xA],
+            layer3_layer_blending: bytes[0xB],
+            liquid_options: bytes[0xC],
+            palette_fx: bytes[0xD],
+            animated_tiles: bytes[0xE],
+            palette_blend: bytes[0xF],
+        }
+    }
+}
+
+/// One state of a room, with the objects placed while it is active.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RoomState {
+    pub condition: StateCondition,
+    /// Address of the state data.
+    pub address: u32,
+    /// Address of the compressed level data.
+    pub level_data: u32,
+    pub tileset: u8,
+    pub music_data: u8,
+    pub music_track: u8,
+    /// Pointer into `FX_BANK`.
+    pub fx_pointer: u16,
+    /// Pointer into `ENEMY_POPULATION_BANK`.
+    pub enemy_population_pointer: u16,
+    /// Pointer into `ENEMY_SET_BANK`.
+    pub enemy_set_pointer: u16,
+    pub layer2_scroll: u16,
+    pub scroll_pointer: u16,
+    pub special_xray: u16,
+    pub main_asm: u16,
+    /// Pointer into `PLM_BANK`.
+    pub plm_set_pointer: u16,
+    pub library_background: u16,
+    pub setup_asm: u16,
+    pub fx: Vec<Fx>,
+    pub enemies: Vec<Enemy>,
+    /// Number of enemies to kill to open the gray doors of the room.
+    pub enemies_to_kill: u8,
+    pub plms: Vec<Plm>,
+}
+
+impl RoomState {
+    /// Parses the `STATE_SIZE` bytes of the state at `address`, the object
+    /// lists are left empty.
+    pub fn from_bytes(condition: StateCondition, address: u32, bytes: &[u8]) -> Self {
+        RoomState {
+            condition,
+            address,
+            level_data: u32::from_le_bytes([bytes[0x0], bytes[0x1], bytes[0x2], 0]),
+            tileset: bytes[0x3],
+            music_data: bytes[0x4],
+            music_track: bytes[0x5],
+            fx_pointer: word(bytes, 0x6),
+            enemy_population_pointer: word(bytes, 0x8),
+            enemy_set_pointer: word(bytes, 0xA),
+            layer2_scroll: word(bytes, 0xC),
+            scroll_pointer: word(bytes, 0xE),
+            special_xray: word(bytes, 0x10),
+            main_asm: word(bytes, 0x12),
+            plm_set_pointer: word(bytes, 0x14),
+            library_background: word(bytes, 0x16),
+            setup_asm: word(bytes, 0x18),
+            fx: Vec::new(),
+            enemies: Vec::new(),
+            enemies_to_kill: 0,
+            plms: Vec::new(),
+        }
+    }
+}
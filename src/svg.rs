@@ -0,0 +1,231 @@
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+use imageproc::point::Point;
+
+use crate::{
+    constants::CELL_SIZE,
+    shapes::Polygon,
+    types::{BlockType, Room, TreatAsSlopeType},
+};
+
+const DEFAULT_STYLE: &str = "\
+path { stroke: none; }
+.solid.treat_solid { fill: #00ff00; }
+.solid.treat_slope_protect_neg_x { fill: #ffff00; }
+.solid.treat_slope_protect_pos_x { fill: #ff00ff; }
+.slope.treat_solid { fill: #00ff00; }
+.slope.treat_slope_left, .slope.treat_slope_protect_neg_x { fill: #ffff00; }
+.slope.treat_slope_right, .slope.treat_slope_protect_pos_x { fill: #ff00ff; }
+.air_xray { fill: none; stroke: #a0a0a0; }
+.treadmill { fill: #00c8c8; }
+.air_shot { fill: none; stroke: #ffdc78; }
+.air_bomb { fill: none; stroke: #ffffff; }
+.door { fill: #0080ff; }
+.spike { fill: #ff0000; }
+.crumble { fill: #ff8000; }
+.shot { fill: #ffdc00; }
+.grapple { fill: #a000ff; }
+.bomb { fill: #ffffff; fill-opacity: 0.5; }
+.room_outline { fill: none; stroke: #00ff00; }
+";
+
+/// Renders the room as an SVG document.
+///
+/// Every `Polygon` becomes one `<path>`, grouped in a `<g>` per block type and
+/// `TreatAsSlopeType`, with class names such as `slope treat_slope_left`
+/// built from `BlockType::name` and `TreatAsSlopeType::name`.
+pub fn render_svg(room: &Room) -> String {
+    let width = room.get_room_width_tiles() as u32 * CELL_SIZE as u32;
+    let height = room.get_room_height_tiles() as u32 * CELL_SIZE as u32;
+
+    // group every polygon by its classes, keeping a stable order
+    let mut groups: BTreeMap<(BlockType, TreatAsSlopeType), Vec<Polygon>> = BTreeMap::new();
+    for cell in &room.cells {
+        let polygons = cell.local_polygons();
+        if polygons.is_empty() {
+            continue;
+        }
+
+        // only solid and slope cells are split by how they are treated, solid cells
+        // the slope next to them runs into are left out like in the png
+        let block_type = cell.effective_block_type();
+        let treat_as_slope = match block_type {
+            BlockType::Solid | BlockType::Slope => cell.treat_as_slope(),
+            _ => TreatAsSlopeType::Solid,
+        };
+        if block_type == BlockType::Solid
+            && matches!(
+                treat_as_slope,
+                TreatAsSlopeType::SlopeLeft | TreatAsSlopeType::SlopeRight
+            )
+        {
+            continue;
+        }
+        let key = (block_type, treat_as_slope);
+        let group = groups.entry(key).or_default();
+        for mut polygon in polygons {
+            if polygon.signed_area() < 0.0 {
+                polygon.points.reverse();
+            }
+            polygon.widen();
+            polygon.translate(
+                cell.x() as f32 * CELL_SIZE as f32,
+                cell.y() as f32 * CELL_SIZE as f32,
+            );
+            group.push(polygon);
+        }
+    }
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+        width, height
+    )
+    .unwrap();
+    writeln!(svg, "<title>{}</title>", escape(&room.room_id)).unwrap();
+    writeln!(svg, "<style>\n{}</style>", DEFAULT_STYLE).unwrap();
+
+    for ((block_type, treat_as_slope), polygons) in groups {
+        let mut class = block_type.name().to_string();
+        if matches!(block_type, BlockType::Solid | BlockType::Slope) {
+            class.push(' ');
+            class.push_str(&treat_class(treat_as_slope));
+        }
+
+        writeln!(svg, r#"<g class="{}">"#, class).unwrap();
+        for polygon in polygons {
+            writeln!(svg, r#"<path d="{}"/>"#, path_data(&polygon)).unwrap();
+        }
+        writeln!(svg, "</g>").unwrap();
+    }
+
+    writeln!(
+        svg,
+        r#"<rect class="room_outline" x="0.5" y="0.5" width="{}" height="{}"/>"#,
+        width - 1,
+        height - 1
+    )
+    .unwrap();
+    writeln!(svg, "</svg>").unwrap();
+
+    svg
+}
+
+pub fn save_svg<P: AsRef<Path>>(room: &Room, path: P) -> std::io::Result<()> {
+    let path = path.as_ref();
+
+    // check if the folder exists
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() && !parent.exists() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+
+    std::fs::write(path, render_svg(room))
+}
+
+fn path_data(polygon: &Polygon) -> String {
+    let mut data = String::new();
+    for (i, Point { x, y }) in polygon.points.iter().enumerate() {
+        let command = if i == 0 { 'M' } else { 'L' };
+        write!(data, "{}{} {} ", command, x, y).unwrap();
+    }
+    data.push('Z');
+    data
+}
+
+fn treat_class(treat_as_slope: TreatAsSlopeType) -> String {
+    format!("treat_{}", treat_as_slope.name())
+}
+
+/// Escapes text for XML content and attribute values.
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::room_from;
+
+    #[test]
+    fn svg_has_one_group_per_class() {
+        // a solid floor, a spike and a 45 degree slope in the top right corner
+        let mut room = room_from(|x, y| match (x, y) {
+            (_, 15) => (0x8000, 0),
+            (3, 14) => (0xA000, 0),
+            (14, 0) => (0x1000, 0x12),
+            (15, 0) => (0x8000, 0),
+            _ => (0, 0),
+        });
+        room.room_id = "<A&B>".to_string();
+
+        let svg = render_svg(&room);
+        assert!(
+            svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="256" height="256""#)
+        );
+        assert!(svg.ends_with("</svg>\n"));
+        assert!(svg.contains("<title>&lt;A&amp;B&gt;</title>"));
+        assert!(svg
+            .contains(r#"<rect class="room_outline" x="0.5" y="0.5" width="255" height="255"/>"#));
+
+        let groups: Vec<(&str, usize)> = svg
+            .split(r#"<g class=""#)
+            .skip(1)
+            .map(|group| {
+                let class = group.split('"').next().unwrap();
+                let paths = group.split("</g>").next().unwrap().matches("<path").count();
+                (class, paths)
+            })
+            .collect();
+        assert_eq!(
+            groups,
+            [
+                ("slope treat_slope_left", 1),
+                ("solid treat_solid", 16),
+                ("spike", 1)
+            ]
+        );
+        assert!(svg.contains(r#"<path d="M16 256 L16 240 L32 240 L32 256 Z"/>"#));
+    }
+
+    #[test]
+    fn solid_cells_a_slope_runs_into_are_left_out() {
+        let room = room_from(|x, y| match (x, y) {
+            (14, 0) => (0x1000, 0x12),
+            (15, 0) => (0x8000, 0),
+            _ => (0, 0),
+        });
+        let cell = room.get_cell(15, 0).unwrap();
+        assert_eq!(cell.treat_as_slope(), TreatAsSlopeType::SlopeLeft);
+
+        let svg = render_svg(&room);
+        assert!(!svg.contains(r#"<g class="solid"#));
+
+        // the png leaves the cell empty as well
+        let img = room.render_image();
+        assert_eq!(img.get_pixel(15 * 16 + 8, 8)[3], 0);
+    }
+
+    #[test]
+    fn class_names() {
+        assert_eq!(BlockType::AirXray.name(), "air_xray");
+        assert_eq!(
+            treat_class(TreatAsSlopeType::SlopeProtectNegX),
+            "treat_slope_protect_neg_x"
+        );
+        for treat_as_slope in TreatAsSlopeType::ALL {
+            assert!(DEFAULT_STYLE.contains(&treat_class(treat_as_slope)));
+        }
+    }
+
+    #[test]
+    fn escape_replaces_markup() {
+        assert_eq!(escape(r#"a<b>&"c""#), "a&lt;b&gt;&amp;&quot;c&quot;");
+        assert_eq!(escape("plain"), "plain");
+    }
+}
@@ -0,0 +1,380 @@
+use std::{fmt::Write, io, path::Path};
+
+use image::{Rgba, RgbaImage};
+use imageproc::drawing::draw_polygon_mut;
+
+use crate::{
+    constants::CELL_SIZE,
+    contours,
+    render::{draw_block, draw_hex_byte, BlockStyle, Pattern, RenderStyle},
+    shapes::SlopeShape,
+    svg::escape,
+    types::{BlockType, Cell, Room, SlopeType},
+};
+
+pub const BLOCKS_TILESET: &str = "blocks.tsx";
+pub const BLOCKS_IMAGE: &str = "blocks.png";
+pub const BTS_TILESET: &str = "bts.tsx";
+pub const BTS_IMAGE: &str = "bts.png";
+
+// gids 1..=16 are the block types, the bts values follow
+const BLOCKS_FIRST_GID: u32 = 1;
+const BTS_FIRST_GID: u32 = BLOCKS_FIRST_GID + 16;
+
+const SOLID_COLOR: Rgba<u8> = Rgba([0, 255, 0, 255]);
+const COPY_COLOR: Rgba<u8> = Rgba([128, 128, 128, 255]);
+const BTS_COLOR: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+/// Renders the room as a Tiled map referencing `BLOCKS_TILESET` and `BTS_TILESET`.
+///
+/// The map has a `block_type` tile layer, a hidden `bts` tile layer and a
+/// `collision` object layer with one polygon per `contours::collision_contours`
+/// contour, holes are marked with a `hole` property.
+pub fn render_tmx(room: &Room) -> String {
+    let width = room.get_room_width_tiles();
+    let height = room.get_room_height_tiles();
+    let contours = contours::collision_contours(room);
+
+    let mut tmx = String::new();
+    writeln!(tmx, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(
+        tmx,
+        r#"<map version="1.10" orientation="orthogonal" renderorder="right-down" width="{}" height="{}" tilewidth="{2}" tileheight="{2}" infinite="0" nextlayerid="4" nextobjectid="{3}">"#,
+        width,
+        height,
+        CELL_SIZE,
+        contours.len() + 1
+    )
+    .unwrap();
+
+    writeln!(tmx, " <properties>").unwrap();
+    write_property(&mut tmx, "room_id", "string", &room.room_id);
+    write_property(&mut tmx, "area", "string", room.area_index().name());
+    write_property(&mut tmx, "area_index", "int", room.area_index().index());
+    write_property(&mut tmx, "room_index", "int", room.room_index());
+    write_property(&mut tmx, "map_x", "int", room.map_x());
+    write_property(&mut tmx, "map_y", "int", room.map_y());
+    writeln!(tmx, " </properties>").unwrap();
+
+    writeln!(
+        tmx,
+        r#" <tileset firstgid="{}" source="{}"/>"#,
+        BLOCKS_FIRST_GID, BLOCKS_TILESET
+    )
+    .unwrap();
+    writeln!(
+        tmx,
+        r#" <tileset firstgid="{}" source="{}"/>"#,
+        BTS_FIRST_GID, BTS_TILESET
+    )
+    .unwrap();
+
+    write_layer(&mut tmx, room, 1, "block_type", true, block_gid);
+    write_layer(&mut tmx, room, 2, "bts", false, bts_gid);
+
+    writeln!(tmx, r#" <objectgroup id="3" name="collision">"#).unwrap();
+    for (i, contour) in contours.iter().enumerate() {
+        // contours use inclusive pixel coordinates, objects end on the pixel edges
+        let mut contour = contour.clone();
+        contour.widen();
+
+        // tiled polygon points are relative to the object position
+        let origin = contour.points[0];
+        writeln!(
+            tmx,
+            r#"  <object id="{}" x="{}" y="{}">"#,
+            i + 1,
+            origin.x,
+            origin.y
+        )
+        .unwrap();
+        if contour.signed_area() < 0.0 {
+            writeln!(tmx, "   <properties>").unwrap();
+            writeln!(
+                tmx,
+                r#"    <property name="hole" type="bool" value="true"/>"#
+            )
+            .unwrap();
+            writeln!(tmx, "   </properties>").unwrap();
+        }
+        let points: Vec<String> = contour
+            .points
+            .iter()
+            .map(|point| format!("{},{}", point.x - origin.x, point.y - origin.y))
+            .collect();
+        writeln!(tmx, r#"   <polygon points="{}"/>"#, points.join(" ")).unwrap();
+        writeln!(tmx, "  </object>").unwrap();
+    }
+    writeln!(tmx, " </objectgroup>").unwrap();
+    writeln!(tmx, "</map>").unwrap();
+
+    tmx
+}
+
+/// The tileset with one tile per `BlockType`, in block type order.
+pub fn render_blocks_tsx() -> String {
+    let mut tsx = tileset_header("blocks", 16, BLOCKS_IMAGE);
+    for block_type in BlockType::ALL {
+        writeln!(tsx, r#" <tile id="{}">"#, block_type as u8).unwrap();
+        writeln!(tsx, "  <properties>").unwrap();
+        writeln!(
+            tsx,
+            r#"   <property name="block_type" value="{}"/>"#,
+            block_type.name()
+        )
+        .unwrap();
+        writeln!(tsx, "  </properties>").unwrap();
+        writeln!(tsx, " </tile>").unwrap();
+    }
+    writeln!(tsx, "</tileset>").unwrap();
+    tsx
+}
+
+/// The tileset with one tile per BTS value, labelled with the value in hex.
+pub fn render_bts_tsx() -> String {
+    let mut tsx = tileset_header("bts", 256, BTS_IMAGE);
+    writeln!(tsx, "</tileset>").unwrap();
+    tsx
+}
+
+/// The image of `render_blocks_tsx`, one row of 16 tiles.
+pub fn blocks_tileset_image() -> RgbaImage {
+    let style = RenderStyle::default();
+    let size = CELL_SIZE as u32;
+    let mut img = RgbaImage::new(16 * size, size);
+
+    for block_type in BlockType::ALL {
+        let x = block_type as u16;
+        match block_type {
+            BlockType::Air => {}
+            BlockType::Solid => {
+                draw_block(&mut img, x, 0, &BlockStyle::new(SOLID_COLOR, Pattern::Fill))
+            }
+            BlockType::Slope => {
+                for mut polygon in SlopeShape::from(SlopeType::Slope45) {
+                    polygon.translate((x * CELL_SIZE) as f32, 0.0);
+                    draw_polygon_mut(&mut img, &polygon.points, SOLID_COLOR);
+                }
+            }
+            _ => {
+                let block_style = style
+                    .block(block_type)
+                    .copied()
+                    .unwrap_or(BlockStyle::new(COPY_COLOR, Pattern::Cross));
+                draw_block(&mut img, x, 0, &block_style);
+            }
+        }
+    }
+
+    img
+}
+
+/// The image of `render_bts_tsx`, 16 rows of 16 tiles.
+pub fn bts_tileset_image() -> RgbaImage {
+    let size = CELL_SIZE as u32;
+    let mut img = RgbaImage::new(16 * size, 16 * size);
+
+    for value in 0..=255u8 {
+        let x = (value % 16) as u32 * size;
+        let y = (value / 16) as u32 * size;
+        draw_hex_byte(&mut img, x + 4, y + 5, value, BTS_COLOR);
+    }
+
+    img
+}
+
+/// Writes both tilesets and their images to `dir`.
+///
+/// Every map in a directory shares them, so they only need to be written once
+/// per directory, before or after the maps.
+pub fn save_tilesets<P: AsRef<Path>>(dir: P) -> io::Result<()> {
+    let dir = dir.as_ref();
+
+    // check if the folder exists
+    if !dir.as_os_str().is_empty() && !dir.exists() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    std::fs::write(dir.join(BLOCKS_TILESET), render_blocks_tsx())?;
+    std::fs::write(dir.join(BTS_TILESET), render_bts_tsx())?;
+    blocks_tileset_image()
+        .save(dir.join(BLOCKS_IMAGE))
+        .map_err(io::Error::other)?;
+    bts_tileset_image()
+        .save(dir.join(BTS_IMAGE))
+        .map_err(io::Error::other)
+}
+
+/// Writes the room as a `.tmx` map, the tilesets are written by `save_tilesets`.
+pub fn save_tmx<P: AsRef<Path>>(room: &Room, path: P) -> io::Result<()> {
+    let path = path.as_ref();
+
+    // check if the folder exists
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() && !parent.exists() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+
+    std::fs::write(path, render_tmx(room))
+}
+
+// air is left empty
+fn block_gid(cell: &Cell) -> u32 {
+    match cell.effective_block_type() {
+        BlockType::Air => 0,
+        block_type => BLOCKS_FIRST_GID + block_type as u32,
+    }
+}
+
+// a zero bts only means something on slopes, leave it empty everywhere else
+fn bts_gid(cell: &Cell) -> u32 {
+    if cell.effective_bts() == 0 && cell.effective_block_type() != BlockType::Slope {
+        0
+    } else {
+        BTS_FIRST_GID + cell.effective_bts() as u32
+    }
+}
+
+fn write_layer(
+    tmx: &mut String,
+    room: &Room,
+    id: u32,
+    name: &str,
+    visible: bool,
+    gid: fn(&Cell) -> u32,
+) {
+    let width = room.get_room_width_tiles() as usize;
+    writeln!(
+        tmx,
+        r#" <layer id="{}" name="{}" width="{}" height="{}" visible="{}">"#,
+        id,
+        name,
+        width,
+        room.get_room_height_tiles(),
+        visible as u8
+    )
+    .unwrap();
+    writeln!(tmx, r#"  <data encoding="csv">"#).unwrap();
+
+    let rows: Vec<String> = room
+        .cells
+        .chunks(width)
+        .map(|row| {
+            row.iter()
+                .map(|cell| gid(cell).to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .collect();
+    writeln!(tmx, "{}", rows.join(",\n")).unwrap();
+
+    writeln!(tmx, "  </data>").unwrap();
+    writeln!(tmx, " </layer>").unwrap();
+}
+
+fn write_property(tmx: &mut String, name: &str, kind: &str, value: impl std::fmt::Display) {
+    writeln!(
+        tmx,
+        r#"  <property name="{}" type="{}" value="{}"/>"#,
+        name,
+        kind,
+        escape(&value.to_string())
+    )
+    .unwrap();
+}
+
+fn tileset_header(name: &str, tile_count: u32, image: &str) -> String {
+    let size = CELL_SIZE as u32;
+    let columns = tile_count.min(16);
+    let rows = tile_count.div_ceil(16);
+
+    let mut tsx = String::new();
+    writeln!(tsx, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(
+        tsx,
+        r#"<tileset version="1.10" name="{name}" tilewidth="{size}" tileheight="{size}" tilecount="{tile_count}" columns="{columns}">"#
+    )
+    .unwrap();
+    writeln!(
+        tsx,
+        r#" <image source="{}" width="{}" height="{}"/>"#,
+        image,
+        columns * size,
+        rows * size
+    )
+    .unwrap();
+    tsx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::room_from;
+
+    #[test]
+    fn map_has_one_gid_per_cell_and_one_object_per_contour() {
+        // a single screen room with a solid floor
+        let room = room_from(|_, y| if y == 15 { (0x8000, 0) } else { (0, 0) });
+
+        let tmx = render_tmx(&room);
+        let block_layer = tmx
+            .split(r#"<data encoding="csv">"#)
+            .nth(1)
+            .and_then(|data| data.split("</data>").next())
+            .unwrap();
+        let gids: Vec<&str> = block_layer.split(',').map(str::trim).collect();
+        assert_eq!(gids.len(), 256);
+        assert_eq!(gids.iter().filter(|gid| **gid == "9").count(), 16);
+        assert_eq!(tmx.matches("<polygon ").count(), 1);
+    }
+
+    #[test]
+    fn objects_cover_whole_cells() {
+        // a single solid cell and a 3x3 ring of solid cells around an air cell
+        let room = room_from(|x, y| match (x, y) {
+            (2, 3) => (0x8000, 0),
+            (9, 9) => (0, 0),
+            (8..=10, 8..=10) => (0x8000, 0),
+            _ => (0, 0),
+        });
+
+        let tmx = render_tmx(&room);
+        let mut objects: Vec<(i32, i32, i32, i32)> = tmx
+            .split("<object ")
+            .skip(1)
+            .map(|object| {
+                let attribute = |name: &str| -> i32 {
+                    let start = object.find(&format!(r#"{}=""#, name)).unwrap() + name.len() + 2;
+                    object[start..].split('"').next().unwrap().parse().unwrap()
+                };
+                let points = object.split(r#"points=""#).nth(1).unwrap();
+                let points: Vec<(i32, i32)> = points
+                    .split('"')
+                    .next()
+                    .unwrap()
+                    .split(' ')
+                    .map(|point| {
+                        let (x, y) = point.split_once(',').unwrap();
+                        (x.parse().unwrap(), y.parse().unwrap())
+                    })
+                    .collect();
+                let (x, y) = (attribute("x"), attribute("y"));
+                let xs = points.iter().map(|point| x + point.0);
+                let ys = points.iter().map(|point| y + point.1);
+                (
+                    xs.clone().min().unwrap(),
+                    ys.clone().min().unwrap(),
+                    xs.max().unwrap(),
+                    ys.max().unwrap(),
+                )
+            })
+            .collect();
+        objects.sort();
+
+        assert_eq!(
+            objects,
+            [(32, 48, 48, 64), (128, 128, 176, 176), (144, 144, 160, 160)]
+        );
+    }
+}
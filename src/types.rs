@@ -1,25 +1,38 @@
-use rand::Rng;
+use log::debug;
 
-use image::Rgba;
+use std::{fmt, path::Path, str::FromStr};
+
+use image::{imageops, ImageResult, Rgba, RgbaImage};
 use imageproc::{
     drawing::{
         draw_filled_rect_mut, draw_hollow_rect_mut, draw_line_segment_mut, draw_polygon_mut,
     },
+    point::Point,
     rect::Rect,
 };
 
 use crate::{
-    constants::{BTS_SLOPE_FLIP_MASK, BTS_SLOPE_TYPE_MASK, CELL_SIZE},
+    constants::{
+        BTS_SLOPE_FLIP_MASK, BTS_SLOPE_TYPE_MASK, CELL_SIZE, LEVEL_BLOCK_TYPE_MASK,
+        LEVEL_FLIP_MASK, LEVEL_HFLIP_MASK, LEVEL_TILE_MASK, LEVEL_VFLIP_MASK, ROOM_HEADER_SIZE,
+    },
+    contours,
+    error::RoomParseError,
+    lz,
+    render::{draw_block, draw_plms, scale_image, RenderStyle, Scale},
     shapes::{
         vectors::{SlopeVectors, Vector},
-        Polygon,
+        Polygon, SlopeShape,
     },
+    slope_runs::{find_slope_runs, SlopeRun},
+    state::{Door, RoomState},
 };
 
-#[derive(Default, Debug, PartialEq)]
-enum AreaIndex {
+#[derive(Default, Debug, PartialEq, Eq, Hash, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AreaIndex {
     #[default]
-    Crateria = 0x0,
+    Crateria,
     Brinstar,
     Norfair,
     WreckedShip,
@@ -27,6 +40,8 @@ enum AreaIndex {
     Tourian,
     Colony,
     Debug,
+    /// An area index the game doesn't use, kept so the header round trips.
+    Unknown(u8),
 }
 
 impl From<u8> for AreaIndex {
@@ -40,12 +55,95 @@ impl From<u8> for AreaIndex {
             0x5 => AreaIndex::Tourian,
             0x6 => AreaIndex::Colony,
             0x7 => AreaIndex::Debug,
-            _ => AreaIndex::Crateria,
+            _ => AreaIndex::Unknown(value),
+        }
+    }
+}
+
+impl AreaIndex {
+    pub const ALL: [AreaIndex; 8] = [
+        AreaIndex::Crateria,
+        AreaIndex::Brinstar,
+        AreaIndex::Norfair,
+        AreaIndex::WreckedShip,
+        AreaIndex::Maridia,
+        AreaIndex::Tourian,
+        AreaIndex::Colony,
+        AreaIndex::Debug,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AreaIndex::Crateria => "crateria",
+            AreaIndex::Brinstar => "brinstar",
+            AreaIndex::Norfair => "norfair",
+            AreaIndex::WreckedShip => "wrecked_ship",
+            AreaIndex::Maridia => "maridia",
+            AreaIndex::Tourian => "tourian",
+            AreaIndex::Colony => "colony",
+            AreaIndex::Debug => "debug",
+            AreaIndex::Unknown(_) => "unknown",
+        }
+    }
+
+    /// The area byte of the room header.
+    pub fn index(&self) -> u8 {
+        match self {
+            AreaIndex::Crateria => 0x0,
+            AreaIndex::Brinstar => 0x1,
+            AreaIndex::Norfair => 0x2,
+            AreaIndex::WreckedShip => 0x3,
+            AreaIndex::Maridia => 0x4,
+            AreaIndex::Tourian => 0x5,
+            AreaIndex::Colony => 0x6,
+            AreaIndex::Debug => 0x7,
+            AreaIndex::Unknown(index) => *index,
+        }
+    }
+}
+
+// unknown areas get their index in hex, so every area has its own name
+impl fmt::Display for AreaIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AreaIndex::Unknown(index) => write!(f, "{}_{:02X}", self.name(), index),
+            _ => f.write_str(self.name()),
+        }
+    }
+}
+
+impl FromStr for AreaIndex {
+    type Err = String;
+
+    // accepts the area name (case and separator insensitive), its decimal
+    // index or its index in hex with a `0x` prefix
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized: String = s
+            .chars()
+            .filter(|c| !matches!(c, '_' | '-' | ' '))
+            .collect::<String>()
+            .to_lowercase();
+
+        if let Some(area) = AreaIndex::ALL
+            .into_iter()
+            .find(|area| area.name().replace('_', "") == normalized)
+        {
+            return Ok(area);
+        }
+
+        let index = match normalized.strip_prefix("0x") {
+            Some(hex) => u8::from_str_radix(hex, 16),
+            None => normalized.parse::<u8>(),
+        };
+        match index {
+            Ok(index) if (index as usize) < AreaIndex::ALL.len() => Ok(AreaIndex::from(index)),
+            _ => Err(format!("unknown area: {}", s)),
         }
     }
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Flip {
     None = 0x0,
     Horizontal,
@@ -65,7 +163,8 @@ impl From<u8> for Flip {
     }
 }
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BlockType {
     Air = 0x0,
     Slope,
@@ -109,16 +208,54 @@ impl From<u8> for BlockType {
     }
 }
 
-fn get_random_color() -> Rgba<u8> {
-    Rgba([
-        rand::thread_rng().gen_range(0..=255),
-        rand::thread_rng().gen_range(0..=255),
-        rand::thread_rng().gen_range(0..=255),
-        255,
-    ])
+impl BlockType {
+    pub const ALL: [BlockType; 16] = [
+        BlockType::Air,
+        BlockType::Slope,
+        BlockType::AirXray,
+        BlockType::Treadmill,
+        BlockType::AirShot,
+        BlockType::HCopy,
+        BlockType::Unused,
+        BlockType::AirBomb,
+        BlockType::Solid,
+        BlockType::Door,
+        BlockType::Spike,
+        BlockType::Crumble,
+        BlockType::Shot,
+        BlockType::VCopy,
+        BlockType::Grapple,
+        BlockType::Bomb,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BlockType::Air => "air",
+            BlockType::Slope => "slope",
+            BlockType::AirXray => "air_xray",
+            BlockType::Treadmill => "treadmill",
+            BlockType::AirShot => "air_shot",
+            BlockType::HCopy => "h_copy",
+            BlockType::Unused => "unused",
+            BlockType::AirBomb => "air_bomb",
+            BlockType::Solid => "solid",
+            BlockType::Door => "door",
+            BlockType::Spike => "spike",
+            BlockType::Crumble => "crumble",
+            BlockType::Shot => "shot",
+            BlockType::VCopy => "v_copy",
+            BlockType::Grapple => "grapple",
+            BlockType::Bomb => "bomb",
+        }
+    }
 }
 
-#[derive(Default)]
+#[derive(Default, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "RoomData")
+)]
 pub struct Room {
     pub room_id: String,
     area_index: AreaIndex,
@@ -137,33 +274,164 @@ pub struct Room {
     unk6: u8,
     unk7: u8,
     pub cells: Vec<Cell>,
-    pub slope_vectors: Vec<VisualDataState>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub slope_runs: Vec<SlopeRun>,
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub slope_union_sets: Vec<usize>,
+    /// The layer 2 tiles in the same order as `cells`, empty when the room has no layer 2.
+    pub layer2: Vec<Layer2Tile>,
+    /// Every state of the room ending with the default one, only known for rooms read from a `Rom`.
+    pub states: Vec<RoomState>,
+    /// The doors leading out of the room, only known for rooms read from a `Rom`.
+    pub doors: Vec<Door>,
 }
 
-struct VisualDataState {
-    treat_as_slope: bool,
+// the serialized form of a `Room`, without the slope data derived from the cells
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RoomData {
+    room_id: String,
+    area_index: AreaIndex,
+    room_index: u8,
+    map_x: u8,
+    map_y: u8,
+    room_width: u8,
+    room_height: u8,
+    up_scroll: u8,
+    down_scroll: u8,
+    special_graphics_bitflag: u8,
+    door_out_pointer: u8,
+    unk3: u8,
+    unk4: u8,
+    unk5: u8,
+    unk6: u8,
+    unk7: u8,
+    cells: Vec<Cell>,
+    #[serde(default)]
+    layer2: Vec<Layer2Tile>,
+    #[serde(default)]
+    states: Vec<RoomState>,
+    #[serde(default)]
+    doors: Vec<Door>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RoomData> for Room {
+    type Error = RoomParseError;
+
+    // only the level words and bts of the cells are kept, everything else is parsed again
+    fn try_from(data: RoomData) -> Result<Self, Self::Error> {
+        let raw = Room {
+            area_index: data.area_index,
+            room_index: data.room_index,
+            map_x: data.map_x,
+            map_y: data.map_y,
+            room_width: data.room_width,
+            room_height: data.room_height,
+            up_scroll: data.up_scroll,
+            down_scroll: data.down_scroll,
+            special_graphics_bitflag: data.special_graphics_bitflag,
+            door_out_pointer: data.door_out_pointer,
+            unk3: data.unk3,
+            unk4: data.unk4,
+            unk5: data.unk5,
+            unk6: data.unk6,
+            unk7: data.unk7,
+            cells: data.cells,
+            layer2: data.layer2,
+            ..Default::default()
+        };
+
+        let mut room = Room::try_from_bytes(&raw.to_bytes())?;
+        room.room_id = data.room_id;
+        room.states = data.states;
+        room.doors = data.doors;
+        Ok(room)
+    }
 }
 
 impl Room {
-    pub fn from_bytes(bytes: &[u8]) -> Self {
+    /// Parses a `.room` file.
+    ///
+    /// The BTS data can be followed by a layer 2 block of 2 bytes per tile.
+    /// Zero bytes after that are padding and ignored, any other trailing data
+    /// is an error. A layer 2 block of only zeros is read as padding too.
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, RoomParseError> {
         // separate header at offset 0x00 and length 0x0E and room data at offset 0x0E non-inclusive
-        let header = &bytes[0x00..=0x0E];
+        if bytes.len() < ROOM_HEADER_SIZE {
+            return Err(RoomParseError::ShortHeader {
+                len: bytes.len(),
+                expected: ROOM_HEADER_SIZE,
+            });
+        }
+        let header = &bytes[0x00..ROOM_HEADER_SIZE];
         let mut room = Room::new_from_bytes(header);
 
+        if room.room_width == 0 || room.room_height == 0 {
+            return Err(RoomParseError::ZeroSized {
+                width: room.room_width,
+                height: room.room_height,
+            });
+        }
+
         // get room width and height
         let room_width = room.get_room_width_tiles();
         let room_height = room.get_room_height_tiles();
-        let total_size = (room_width * room_height) as usize;
+        let total_size = room_width as usize * room_height as usize;
 
         // [header][room_data][room_type_data][room_bts_data][unk_data]
-        let raw_data = &bytes[0x0F..];
-        let room_type_data = &raw_data[0x00..total_size * 2];
-        let room_bts_data = &raw_data[(total_size * 2)..];
+        let level_start = ROOM_HEADER_SIZE;
+        let level_end = level_start + total_size * 2;
+        if bytes.len() < level_end {
+            return Err(RoomParseError::TruncatedLevelData {
+                offset: level_start,
+                expected_end: level_end,
+                actual_end: bytes.len(),
+            });
+        }
 
-        println!("room width: {}, room height: {}", room_width, room_height);
+        let bts_end = level_end + total_size;
+        if bytes.len() < bts_end {
+            return Err(RoomParseError::TruncatedBtsData {
+                offset: level_end,
+                expected_end: bts_end,
+                actual_end: bytes.len(),
+            });
+        }
+
+        // anything after the bts is an optional layer 2 block and zero padding,
+        // an all zero block is padding as well
+        let trailing = &bytes[bts_end..];
+        let layer2_end = if trailing.len() >= total_size * 2
+            && trailing[..total_size * 2].iter().any(|byte| *byte != 0)
+        {
+            bts_end + total_size * 2
+        } else {
+            bts_end
+        };
+        let padding = &bytes[layer2_end..];
+        if padding.iter().any(|byte| *byte != 0) {
+            return Err(RoomParseError::TrailingDataMismatch {
+                offset: bts_end,
+                len: trailing.len(),
+                expected: total_size * 2,
+            });
+        }
+        if !padding.is_empty() {
+            debug!(
+                "ignoring {:#X} bytes of padding at {:#X}",
+                padding.len(),
+                layer2_end
+            );
+        }
 
-        // get block type, flip, and that's it for now
+        let room_type_data = &bytes[level_start..level_end];
+        let room_bts_data = &bytes[level_end..bts_end];
+        let layer2_data = &bytes[bts_end..layer2_end];
+
+        debug!("room width: {}, room height: {}", room_width, room_height);
+
+        // get block type, flip and tile
         for (i, byte_pair) in room_type_data.chunks_exact(2).enumerate() {
             let room_width = room_width as usize;
             let room_height = room_height as usize;
@@ -172,11 +440,10 @@ impl Room {
                 break;
             }
 
-            // byte_pair[1] 0000        00      00
-            //              ^block type ^flip   ^??
-            let mut room_cell = Cell::new();
-            room_cell.block_type = BlockType::from((byte_pair[1] & 0b11110000) >> 4);
-            room_cell.flip = Flip::from((byte_pair[1] & 0b1100) >> 2);
+            // level word 0000        0     0     00 0000 0000
+            //            ^block type ^vflip ^hflip ^tile
+            let level_word = u16::from_le_bytes([byte_pair[0], byte_pair[1]]);
+            let mut room_cell = Cell::from_level_word(level_word);
 
             // get x and y
             room_cell.x = (i % room_width) as u16;
@@ -192,17 +459,115 @@ impl Room {
             }
 
             room.cells[i].bts = *byte;
+        }
+
+        // get layer 2 tiles, laid out like the level words
+        for (i, byte_pair) in layer2_data.chunks_exact(2).enumerate() {
+            let room_width = room_width as usize;
+            let mut tile =
+                Layer2Tile::from_level_word(u16::from_le_bytes([byte_pair[0], byte_pair[1]]));
+            tile.x = (i % room_width) as u16;
+            tile.y = (i / room_width) as u16;
+            room.layer2.push(tile);
+        }
+
+        room.resolve_copies();
 
-            if room.cells[i].block_type == BlockType::Slope {
-                let slope_type = room.cells[i].get_slope_type();
-                let vectors = SlopeVectors::from(slope_type);
-                room.cells[i].slope_vectors.extend_from_slice(vectors);
+        for cell in room.cells.iter_mut() {
+            if cell.effective_block_type == BlockType::Slope {
+                let vectors = SlopeVectors::from(cell.get_slope_type());
+                cell.slope_vectors.extend(vectors);
             }
         }
 
         room.set_data_visual();
 
-        room
+        let room_width = room.get_room_width_tiles() as usize;
+        let (slope_union_sets, slope_runs) = find_slope_runs(&room.cells, room_width);
+        room.slope_union_sets = slope_union_sets;
+        room.slope_runs = slope_runs;
+
+        Ok(room)
+    }
+
+    /// Serializes the room back into the `.room` layout read by `try_from_bytes`.
+    ///
+    /// The output is byte-for-byte identical to the parsed input, as long as
+    /// the input had no padding.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let total_size = self.cells.len();
+        let mut bytes =
+            Vec::with_capacity(ROOM_HEADER_SIZE + total_size * 3 + self.layer2.len() * 2);
+
+        // [header][room_type_data][room_bts_data][layer2_data]
+        bytes.extend_from_slice(&[
+            self.area_index.index(),
+            self.room_index,
+            self.map_x,
+            self.map_y,
+            self.room_width,
+            self.room_height,
+            self.up_scroll,
+            self.down_scroll,
+            self.special_graphics_bitflag,
+            self.door_out_pointer,
+            self.unk3,
+            self.unk4,
+            self.unk5,
+            self.unk6,
+            self.unk7,
+        ]);
+
+        for cell in &self.cells {
+            bytes.extend_from_slice(&cell.level_word().to_le_bytes());
+        }
+
+        bytes.extend(self.cells.iter().map(|cell| cell.bts));
+        for tile in &self.layer2 {
+            bytes.extend_from_slice(&tile.level_word().to_le_bytes());
+        }
+
+        bytes
+    }
+
+    /// Parses a room from its 15 byte header and the compressed level data as
+    /// stored in the ROM, which starts with the 2 byte size of the level words.
+    ///
+    /// The header must be exactly 15 bytes, anything else would shift the
+    /// level data.
+    pub fn try_from_compressed(header: &[u8], compressed: &[u8]) -> Result<Self, RoomParseError> {
+        if header.len() < ROOM_HEADER_SIZE {
+            return Err(RoomParseError::ShortHeader {
+                len: header.len(),
+                expected: ROOM_HEADER_SIZE,
+            });
+        }
+        if header.len() > ROOM_HEADER_SIZE {
+            return Err(RoomParseError::LongHeader {
+                len: header.len(),
+                expected: ROOM_HEADER_SIZE,
+            });
+        }
+
+        let level_data = lz::decompress(compressed).map_err(RoomParseError::Compression)?;
+
+        let mut bytes = header.to_vec();
+        bytes.extend_from_slice(level_data.get(2..).unwrap_or_default());
+        Room::try_from_bytes(&bytes)
+    }
+
+    /// The level data in the game's format: the size of the level words, the
+    /// level words, the bts and the layer 2 data if the room has any.
+    pub fn level_data(&self) -> Vec<u8> {
+        let bytes = self.to_bytes();
+        let mut level_data = ((self.cells.len() * 2) as u16).to_le_bytes().to_vec();
+        level_data.extend_from_slice(&bytes[ROOM_HEADER_SIZE..]);
+        level_data
+    }
+
+    /// `level_data` compressed, the counterpart of `try_from_compressed`.
+    pub fn compressed_level_data(&self) -> Vec<u8> {
+        lz::compress(&self.level_data())
     }
 
     fn new_from_bytes(bytes: &[u8]) -> Self {
@@ -224,17 +589,129 @@ impl Room {
             unk6: bytes[0x0D],
             unk7: bytes[0x0E],
             cells: Vec::new(),
-            slope_vectors: Vec::new(),
+            slope_runs: Vec::new(),
             slope_union_sets: Vec::new(),
+            layer2: Vec::new(),
+            states: Vec::new(),
+            doors: Vec::new(),
         }
     }
 
     pub fn get_room_width_tiles(&self) -> u16 {
-        (self.room_width * CELL_SIZE as u8).into()
+        self.room_width as u16 * CELL_SIZE
     }
 
     pub fn get_room_height_tiles(&self) -> u16 {
-        (self.room_height * CELL_SIZE as u8).into()
+        self.room_height as u16 * CELL_SIZE
+    }
+
+    pub fn area_index(&self) -> AreaIndex {
+        self.area_index
+    }
+
+    pub fn room_index(&self) -> u8 {
+        self.room_index
+    }
+
+    pub fn map_x(&self) -> u8 {
+        self.map_x
+    }
+
+    pub fn map_y(&self) -> u8 {
+        self.map_y
+    }
+
+    /// Room width in screens, see `get_room_width_tiles` for the width in tiles.
+    pub fn room_width(&self) -> u8 {
+        self.room_width
+    }
+
+    /// Room height in screens, see `get_room_height_tiles` for the height in tiles.
+    pub fn room_height(&self) -> u8 {
+        self.room_height
+    }
+
+    pub fn up_scroll(&self) -> u8 {
+        self.up_scroll
+    }
+
+    pub fn down_scroll(&self) -> u8 {
+        self.down_scroll
+    }
+
+    pub fn special_graphics_bitflag(&self) -> u8 {
+        self.special_graphics_bitflag
+    }
+
+    pub fn door_out_pointer(&self) -> u8 {
+        self.door_out_pointer
+    }
+
+    /// Header bytes 0x0A..=0x0E, whose meaning is not known yet.
+    pub fn unknown_header_bytes(&self) -> [u8; 5] {
+        [self.unk3, self.unk4, self.unk5, self.unk6, self.unk7]
+    }
+
+    pub fn get_cell(&self, x: u16, y: u16) -> Option<&Cell> {
+        let room_width = self.get_room_width_tiles();
+        if x >= room_width || y >= self.get_room_height_tiles() {
+            return None;
+        }
+        self.cells
+            .get(y as usize * room_width as usize + x as usize)
+    }
+
+    pub fn has_layer2(&self) -> bool {
+        !self.layer2.is_empty()
+    }
+
+    /// The layer 2 tile at `x`, `y`, `None` outside the room or without a layer 2.
+    pub fn get_layer2_tile(&self, x: u16, y: u16) -> Option<&Layer2Tile> {
+        let room_width = self.get_room_width_tiles();
+        if x >= room_width || y >= self.get_room_height_tiles() {
+            return None;
+        }
+        self.layer2
+            .get(y as usize * room_width as usize + x as usize)
+    }
+
+    // follow HCopy and VCopy blocks to the block they copy their type and bts from
+    fn resolve_copies(&mut self) {
+        let room_width = self.get_room_width_tiles() as isize;
+        let total_size = self.cells.len() as isize;
+
+        for i in 0..self.cells.len() {
+            let mut visited = Vec::new();
+            let mut index = i;
+
+            let resolved = loop {
+                let cell = &self.cells[index];
+                // the bts of a copy block is a signed offset in blocks (HCopy) or rows (VCopy)
+                let offset = match cell.block_type {
+                    BlockType::HCopy => cell.bts as i8 as isize,
+                    BlockType::VCopy => cell.bts as i8 as isize * room_width,
+                    _ => break Some((cell.block_type, cell.bts)),
+                };
+
+                if visited.contains(&index) {
+                    debug!("copy block cycle starting at cell {}", i);
+                    break None;
+                }
+                visited.push(index);
+
+                let next = index as isize + offset;
+                if next < 0 || next >= total_size {
+                    debug!("copy block at cell {} points outside the room", i);
+                    break None;
+                }
+                index = next as usize;
+            };
+
+            // broken chains behave like air
+            let (block_type, bts) = resolved.unwrap_or((BlockType::Air, 0));
+            self.cells[i].effective_block_type = block_type;
+            self.cells[i].effective_bts = bts;
+        }
     }
 
     fn set_data_visual(&mut self) -> SlopeType {
@@ -242,13 +719,13 @@ impl Room {
         let room_height = self.get_room_height_tiles() as usize;
 
         for i in 0..(room_width * room_height) {
-            if self.cells[i].block_type != BlockType::Slope
+            if self.cells[i].effective_block_type != BlockType::Slope
                 || self.cells[i].get_slope_type() == SlopeType::Square
             {
                 continue;
             }
 
-            // (self.cells[i].block_type == BlockType::Slope && self.cells[i].get_slope_type() != SlopeType::Square)
+            // (self.cells[i].effective_block_type == BlockType::Slope && self.cells[i].get_slope_type() != SlopeType::Square)
             let slope_flip = self.cells[i].get_slope_flip();
             if slope_flip == Flip::None || slope_flip == Flip::Both {
                 self.cells[i].treat_as_slope = TreatAsSlopeType::SlopeLeft;
@@ -308,161 +785,311 @@ impl Room {
         SlopeType::None
     }
 
-    pub fn save_image(self) {
+    /// The collision of every cell merged into room-wide outer contours and holes.
+    pub fn collision_contours(&self) -> Vec<Polygon> {
+        contours::collision_contours(self)
+    }
+
+    /// `collision_contours`, simplified to within `tolerance` pixels.
+    pub fn collision_contours_with_tolerance(&self, tolerance: f32) -> Vec<Polygon> {
+        contours::collision_contours_with_tolerance(self, tolerance)
+    }
+
+    pub fn render_image(&self) -> RgbaImage {
+        self.render_image_with_style(&RenderStyle::default())
+    }
+
+    pub fn render_image_with_style(&self, style: &RenderStyle) -> RgbaImage {
+        self.render_image_at(style, 1)
+    }
+
+    // every tile is `factor` times `CELL_SIZE` pixels wide. the collision is drawn at
+    // the full size so slopes stay smooth, everything else is drawn at the normal
+    // size and enlarged with nearest neighbour
+    fn render_image_at(&self, style: &RenderStyle, factor: u32) -> RgbaImage {
         let room_width = self.get_room_width_tiles() as usize;
         let room_height = self.get_room_height_tiles() as usize;
+        let size = CELL_SIZE as u32 * factor;
         let mut img = image::ImageBuffer::<image::Rgba<u8>, Vec<u8>>::new(
-            16 * room_width as u32,
-            16 * room_height as u32,
+            size * room_width as u32,
+            size * room_height as u32,
         );
 
+        // inclusive pixel coordinates, the last pixel of a cell stands for its edge
+        let last = CELL_SIZE as i32 - 1;
+        let scale = |value: i32| {
+            if value == last {
+                size as i32 - 1
+            } else {
+                value * factor as i32
+            }
+        };
+        let cell_rect = |cell: &Cell| {
+            Rect::at((cell.x as u32 * size) as i32, (cell.y as u32 * size) as i32)
+                .of_size(size, size)
+        };
+
+        // the erosion below keeps everything next to solid cells
+        let solid = style.collision_color(TreatAsSlopeType::Solid, None);
+
         // get slope vectors and draw a line
         self.cells
             .iter()
             .filter(|cell| {
-                (cell.block_type == BlockType::Solid || (cell.block_type == BlockType::Slope))
+                cell.effective_block_type == BlockType::Solid
+                    || cell.effective_block_type == BlockType::Slope
             })
             .for_each(|cell| {
-                let color = match cell.treat_as_slope {
-                    TreatAsSlopeType::Solid => Rgba([0, 255, 0, 255]),
-                    TreatAsSlopeType::SlopeLeft => Rgba([255, 255, 0, 255]),
-                    TreatAsSlopeType::SlopeRight => Rgba([255, 0, 255, 255]),
-                    TreatAsSlopeType::SlopeProtectNegX => Rgba([255, 255, 0, 255]),
-                    TreatAsSlopeType::SlopeProtectPosX => Rgba([255, 0, 255, 255]),
-                    _ => get_random_color(),
-                };
+                let slope_type =
+                    (cell.effective_block_type == BlockType::Slope).then(|| cell.get_slope_type());
+                let color = style.collision_color(cell.treat_as_slope, slope_type);
 
-                match cell.block_type {
+                match cell.effective_block_type {
                     BlockType::Slope => {
-                        let slope_type = cell.get_slope_type();
-                        let slope_flip = cell.get_slope_flip();
-
-                        let mut shape = Polygon::from(slope_type);
-
-                        match slope_flip {
-                            Flip::None => {}
-                            Flip::Horizontal => {
-                                shape.mirror_x();
-                            }
-                            Flip::Vertical => {
-                                shape.mirror_y();
-                            }
-                            Flip::Both => {
-                                shape.mirror_x();
-                                shape.mirror_y();
-                            }
+                        let (x0, y0) = (cell.x as i32 * size as i32, cell.y as i32 * size as i32);
+                        for shape in cell.local_polygons() {
+                            let points: Vec<Point<i32>> = shape
+                                .points
+                                .iter()
+                                .map(|point| Point::new(x0 + scale(point.x), y0 + scale(point.y)))
+                                .collect();
+                            draw_polygon_mut(&mut img, &points, color);
                         }
-
-                        // add the shape into position
-                        shape.translate(
-                            cell.x as f32 * CELL_SIZE as f32,
-                            cell.y as f32 * CELL_SIZE as f32,
-                        );
-
-                        draw_polygon_mut(&mut img, &shape.points, color);
                     }
                     BlockType::Solid => match cell.treat_as_slope {
                         TreatAsSlopeType::Solid => {
-                            draw_filled_rect_mut(
-                                &mut img,
-                                Rect::at((cell.x * CELL_SIZE).into(), (cell.y * CELL_SIZE).into())
-                                    .of_size(CELL_SIZE.into(), CELL_SIZE.into()),
-                                color,
-                            );
+                            draw_filled_rect_mut(&mut img, cell_rect(cell), color);
                         }
                         TreatAsSlopeType::SlopeRight | TreatAsSlopeType::SlopeLeft => {}
                         TreatAsSlopeType::SlopeProtectNegX => {
+                            let rect = cell_rect(cell);
+                            draw_filled_rect_mut(&mut img, rect, color);
                             draw_filled_rect_mut(
                                 &mut img,
-                                Rect::at((cell.x * CELL_SIZE).into(), (cell.y * CELL_SIZE).into())
-                                    .of_size(CELL_SIZE.into(), CELL_SIZE.into()),
-                                color,
-                            );
-                            let start = ((cell.x * CELL_SIZE).into(), (cell.y * CELL_SIZE).into());
-                            let end = (
-                                (cell.x * CELL_SIZE).into(),
-                                (cell.y * CELL_SIZE + CELL_SIZE - 1).into(),
+                                Rect::at(rect.left(), rect.top()).of_size(factor, size),
+                                solid,
                             );
-                            draw_line_segment_mut(&mut img, start, end, Rgba([0, 255, 0, 255]));
                         }
                         TreatAsSlopeType::SlopeProtectPosX => {
+                            let rect = cell_rect(cell);
+                            draw_filled_rect_mut(&mut img, rect, color);
                             draw_filled_rect_mut(
                                 &mut img,
-                                Rect::at((cell.x * CELL_SIZE).into(), (cell.y * CELL_SIZE).into())
-                                    .of_size(CELL_SIZE.into(), CELL_SIZE.into()),
-                                color,
-                            );
-                            let start = (
-                                (cell.x * CELL_SIZE + CELL_SIZE - 1).into(),
-                                (cell.y * CELL_SIZE).into(),
+                                Rect::at(rect.right() + 1 - factor as i32, rect.top())
+                                    .of_size(factor, size),
+                                solid,
                             );
-                            let end = (
-                                (cell.x * CELL_SIZE + CELL_SIZE - 1).into(),
-                                (cell.y * CELL_SIZE + CELL_SIZE - 1).into(),
-                            );
-                            draw_line_segment_mut(&mut img, start, end, Rgba([0, 255, 0, 255]));
                         }
                     },
                     _ => {}
                 }
             });
 
-        // remove every red pixel which has one transparent horizontal neighboring pixel, repeat 10 times
-        for _ in 0..10 {
-            let img_copy = img.clone();
-            for y in 0..img.height() {
-                for x in 0..img.width() {
-                    if x == 0 || x == 16 * room_width as u32 - 1 {
-                        continue;
-                    }
-                    if y == 0 || y == 16 * room_height as u32 - 1 {
-                        continue;
-                    }
-
-                    let pixel = img_copy.get_pixel(x, y);
-
-                    if pixel.0 == [0, 255, 0, 255] {
-                        continue;
-                    }
-
-                    let left_pixel = img_copy.get_pixel(x - 1, y);
-                    let right_pixel = img_copy.get_pixel(x + 1, y);
-                    let up_pixel = img_copy.get_pixel(x, y - 1);
-                    let down_pixel = img_copy.get_pixel(x, y + 1);
-
+        // remove every red pixel which has one transparent horizontal neighboring pixel,
+        // repeat 10 times. only the pixels drawn in another colour than solid can go
+        let (width, height) = img.dimensions();
+        let mut candidates: Vec<(u32, u32)> = img
+            .enumerate_pixels()
+            .filter(|(x, y, pixel)| {
+                pixel[3] != 0
+                    && **pixel != solid
+                    && *x != 0
+                    && *x != width - 1
+                    && *y != 0
+                    && *y != height - 1
+            })
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        for _ in 0..10 * factor {
+            let removed: Vec<bool> = candidates
+                .iter()
+                .map(|&(x, y)| {
+                    let pixel = img.get_pixel(x, y);
+                    let left_pixel = img.get_pixel(x - 1, y);
+                    let right_pixel = img.get_pixel(x + 1, y);
+                    let up_pixel = img.get_pixel(x, y - 1);
+                    let down_pixel = img.get_pixel(x, y + 1);
+
+                    let mut remove = false;
                     if (pixel.0 != left_pixel.0 || pixel.0 != right_pixel.0)
-                        && (left_pixel.0 != [0, 255, 0, 255] || right_pixel.0 != [0, 255, 0, 255])
+                        && (*left_pixel != solid || *right_pixel != solid)
                     {
-                        if up_pixel[3] == 0 && down_pixel.0 == [0, 255, 0, 255] {
-                            img.put_pixel(x, y, Rgba([0, 0, 0, 0]));
+                        if up_pixel[3] == 0 && *down_pixel == solid {
+                            remove = true;
                         }
 
-                        if down_pixel[3] == 0 && up_pixel.0 == [0, 255, 0, 255] {
-                            img.put_pixel(x, y, Rgba([0, 0, 0, 0]));
+                        if down_pixel[3] == 0 && *up_pixel == solid {
+                            remove = true;
                         }
                     }
 
                     if left_pixel[3] == 0 || right_pixel[3] == 0 {
-                        img.put_pixel(x, y, Rgba([0, 0, 0, 0]));
+                        remove = true;
                     }
+                    remove
+                })
+                .collect();
+            if !removed.contains(&true) {
+                break;
+            }
+
+            let mut removed = removed.into_iter();
+            candidates.retain(|&(x, y)| {
+                if removed.next().unwrap() {
+                    img.put_pixel(x, y, Rgba([0, 0, 0, 0]));
+                    false
+                } else {
+                    true
                 }
+            });
+        }
+
+        let enlarge = |img: &RgbaImage| {
+            imageops::resize(
+                img,
+                img.width() * factor,
+                img.height() * factor,
+                imageops::FilterType::Nearest,
+            )
+        };
+
+        // put the collision on top of the faded layer 2, after the erosion which
+        // relies on everything around the collision being transparent
+        if let Some(color) = style.layer2 {
+            if self.has_layer2() {
+                let mut background = self.render_layer2(color);
+                if factor > 1 {
+                    background = enlarge(&background);
+                }
+                imageops::overlay(&mut background, &img, 0, 0);
+                img = background;
+            }
+        }
+
+        // everything else goes on its own layer, copied over the collision at the end
+        let collision = img;
+        let mut img = RgbaImage::new(16 * room_width as u32, 16 * room_height as u32);
+
+        // draw every other block type on top of the collision
+        for cell in &self.cells {
+            if matches!(
+                cell.effective_block_type,
+                BlockType::Solid | BlockType::Slope
+            ) {
+                continue;
+            }
+
+            if let Some(block_style) = style.block(cell.effective_block_type) {
+                draw_block(&mut img, cell.x, cell.y, block_style);
+            }
+        }
+
+        // draw every slope run as one line
+        if let Some(color) = style.slope_runs {
+            for run in &self.slope_runs {
+                draw_line_segment_mut(
+                    &mut img,
+                    (run.start.x as f32, run.start.y as f32),
+                    (run.end.x as f32, run.end.y as f32),
+                    color,
+                );
             }
         }
+
         // draw room outline on borders
         draw_hollow_rect_mut(
             &mut img,
             Rect::at(0, 0).of_size(16 * room_width as u32, 16 * room_height as u32),
-            Rgba([0, 255, 0, 255]),
+            style.outline,
         );
 
+        // plms of the default state go on top of everything
+        if let Some(state) = self.states.last() {
+            draw_plms(&mut img, &state.plms, style);
+        }
+
+        if factor > 1 {
+            img = enlarge(&img);
+        }
+        let mut output = collision;
+        for (pixel, top) in output.pixels_mut().zip(img.pixels()) {
+            if top[3] != 0 {
+                *pixel = *top;
+            }
+        }
+
+        output
+    }
+
+    // every layer 2 tile filled with a shade of `color`, tiles with the same
+    // number share a shade so the shapes of the background stay visible, tile 0
+    // is left empty
+    fn render_layer2(&self, color: Rgba<u8>) -> RgbaImage {
+        let mut img = RgbaImage::new(
+            self.get_room_width_tiles() as u32 * CELL_SIZE as u32,
+            self.get_room_height_tiles() as u32 * CELL_SIZE as u32,
+        );
+
+        for tile in self.layer2.iter().filter(|tile| tile.tile() != 0) {
+            let shade = 0.5 + 0.5 * ((tile.tile() as u32 * 37) % 16) as f32 / 15.0;
+            let [r, g, b, a] = color.0;
+            let shaded = Rgba([
+                (r as f32 * shade) as u8,
+                (g as f32 * shade) as u8,
+                (b as f32 * shade) as u8,
+                a,
+            ]);
+            draw_filled_rect_mut(
+                &mut img,
+                Rect::at((tile.x * CELL_SIZE).into(), (tile.y * CELL_SIZE).into())
+                    .of_size(CELL_SIZE.into(), CELL_SIZE.into()),
+                shaded,
+            );
+        }
+
+        img
+    }
+
+    pub fn save_image<P: AsRef<Path>>(&self, path: P) -> ImageResult<()> {
+        self.save_image_with_style(path, &RenderStyle::default())
+    }
+
+    pub fn save_image_with_style<P: AsRef<Path>>(
+        &self,
+        path: P,
+        style: &RenderStyle,
+    ) -> ImageResult<()> {
+        self.save_image_scaled(path, style, Scale::default())
+    }
+
+    /// `render_image_with_style` at the given scale.
+    ///
+    /// With `Scale::Pixels` the collision polygons are rasterized at the larger
+    /// size so slopes stay smooth, block patterns and overlays are enlarged with
+    /// nearest neighbour. Thumbnails are resized with `render::scale_image`.
+    pub fn render_image_scaled(&self, style: &RenderStyle, scale: Scale) -> RgbaImage {
+        match scale {
+            Scale::Pixels(factor) => self.render_image_at(style, factor.max(1)),
+            Scale::TilesPerPixel(_) => scale_image(&self.render_image_with_style(style), scale),
+        }
+    }
+
+    pub fn save_image_scaled<P: AsRef<Path>>(
+        &self,
+        path: P,
+        style: &RenderStyle,
+        scale: Scale,
+    ) -> ImageResult<()> {
+        let path = path.as_ref();
+
         // check if the folder exists
-        let my_path = std::path::Path::new("./output");
-        if !my_path.exists() {
-            std::fs::create_dir(my_path).unwrap();
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                std::fs::create_dir_all(parent)?;
+            }
         }
 
-        // save image
-        img.save(format!("./output/{}.png", self.room_id)).unwrap();
+        self.render_image_scaled(style, scale).save(path)
     }
 }
 
@@ -511,16 +1138,18 @@ pub fn sign(x: f32) -> f32 {
 }
 
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Cell {
     x: u16,
     y: u16,
     treat_as_slope: TreatAsSlopeType,
     pub block_type: BlockType,
+    effective_block_type: BlockType,
     flip: Flip,
-    sprite: u8,
-    palette: u8,
-    unk: u8,
+    tile: u16,
     bts: u8,
+    effective_bts: u8,
+    #[cfg_attr(feature = "serde", serde(skip))]
     slope_vectors: Vec<Vector>,
 }
 
@@ -531,17 +1160,157 @@ impl Cell {
             y: 0,
             treat_as_slope: TreatAsSlopeType::Solid,
             block_type: BlockType::Air,
+            effective_block_type: BlockType::Air,
             flip: Flip::None,
-            sprite: 0,
-            palette: 0,
-            unk: 0,
+            tile: 0,
             bts: 0,
+            effective_bts: 0,
             slope_vectors: Vec::new(),
         }
     }
 
+    /// Decodes the tile number, flip bits and block type of a level data word.
+    pub fn from_level_word(word: u16) -> Self {
+        let mut cell = Cell::new();
+        cell.block_type = BlockType::from(((word & LEVEL_BLOCK_TYPE_MASK) >> 12) as u8);
+        cell.effective_block_type = cell.block_type;
+        cell.flip = Flip::from(((word & LEVEL_FLIP_MASK) >> 10) as u8);
+        cell.tile = word & LEVEL_TILE_MASK;
+        cell
+    }
+
+    pub fn x(&self) -> u16 {
+        self.x
+    }
+
+    pub fn y(&self) -> u16 {
+        self.y
+    }
+
+    pub fn flip(&self) -> Flip {
+        self.flip
+    }
+
+    pub fn is_flipped_horizontally(&self) -> bool {
+        self.level_word() & LEVEL_HFLIP_MASK != 0
+    }
+
+    pub fn is_flipped_vertically(&self) -> bool {
+        self.level_word() & LEVEL_VFLIP_MASK != 0
+    }
+
+    /// Index of the 16x16 metatile drawn in this cell.
+    pub fn tile(&self) -> u16 {
+        self.tile
+    }
+
+    pub fn bts(&self) -> u8 {
+        self.bts
+    }
+
+    /// Block type after following HCopy and VCopy blocks to their source.
+    ///
+    /// Copy blocks whose chain loops or leaves the room resolve to `BlockType::Air`.
+    pub fn effective_block_type(&self) -> BlockType {
+        self.effective_block_type
+    }
+
+    /// Bts after following HCopy and VCopy blocks to their source.
+    pub fn effective_bts(&self) -> u8 {
+        self.effective_bts
+    }
+
+    pub fn treat_as_slope(&self) -> TreatAsSlopeType {
+        self.treat_as_slope
+    }
+
+    pub fn slope_vectors(&self) -> &[Vector] {
+        &self.slope_vectors
+    }
+
+    /// The 16-bit level data word this cell was parsed from.
+    pub fn level_word(&self) -> u16 {
+        (self.block_type as u16) << 12 | (self.flip as u16) << 10 | self.tile
+    }
+
+    /// The shape of the cell in cell-local pixel coordinates, with the slope flip applied.
+    ///
+    /// Slopes use their `SlopeShape`, `BlockType::Air` and `BlockType::Unused`
+    /// are empty and every other block type covers the whole cell.
+    pub fn local_polygons(&self) -> Vec<Polygon> {
+        match self.effective_block_type {
+            BlockType::Air | BlockType::Unused => Vec::new(),
+            BlockType::Slope => {
+                let slope_flip = self.get_slope_flip();
+                let mut shapes = SlopeShape::from(self.get_slope_type());
+
+                for shape in shapes.iter_mut() {
+                    match slope_flip {
+                        Flip::None => {}
+                        Flip::Horizontal => {
+                            shape.mirror_x();
+                        }
+                        Flip::Vertical => {
+                            shape.mirror_y();
+                        }
+                        Flip::Both => {
+                            shape.mirror_x();
+                            shape.mirror_y();
+                        }
+                    }
+                }
+
+                shapes
+            }
+            _ => vec![Polygon::square()],
+        }
+    }
+
+    /// The slope vectors of the cell with the slope flip applied, in room pixel coordinates.
+    pub fn surface_vectors(&self) -> Vec<Vector> {
+        let slope_flip = self.get_slope_flip();
+        let flip_x = matches!(slope_flip, Flip::Horizontal | Flip::Both);
+        let flip_y = matches!(slope_flip, Flip::Vertical | Flip::Both);
+        let origin_x = self.x as i32 * CELL_SIZE as i32;
+        let origin_y = self.y as i32 * CELL_SIZE as i32;
+
+        let place = |point: Point<i32>| {
+            let x = if flip_x {
+                CELL_SIZE as i32 - point.x
+            } else {
+                point.x
+            };
+            let y = if flip_y {
+                CELL_SIZE as i32 - point.y
+            } else {
+                point.y
+            };
+            Point::new(origin_x + x, origin_y + y)
+        };
+
+        self.slope_vectors
+            .iter()
+            .map(|vector| Vector::new(place(vector.start), place(vector.end)))
+            .collect()
+    }
+
+    /// Same as `local_polygons`, moved into room pixel coordinates.
+    pub fn polygons(&self) -> Vec<Polygon> {
+        let mut shapes = self.local_polygons();
+
+        // add the shape into position
+        for shape in shapes.iter_mut() {
+            shape.translate(
+                self.x as f32 * CELL_SIZE as f32,
+                self.y as f32 * CELL_SIZE as f32,
+            );
+        }
+
+        shapes
+    }
+
     pub fn get_slope_flip(&self) -> Flip {
-        match (self.bts & BTS_SLOPE_FLIP_MASK) >> 6 {
+        match (self.effective_bts & BTS_SLOPE_FLIP_MASK) >> 6 {
             0x0 => Flip::None,
             0x1 => Flip::Horizontal,
             0x2 => Flip::Vertical,
@@ -551,17 +1320,59 @@ impl Cell {
     }
 
     pub fn get_slope_type(&self) -> SlopeType {
-        (self.bts & BTS_SLOPE_TYPE_MASK).into()
+        (self.effective_bts & BTS_SLOPE_TYPE_MASK).into()
     }
 
     pub fn is_square(&self) -> bool {
         let slope_type = self.get_slope_type();
-        self.block_type == BlockType::Solid
-            || (self.block_type == BlockType::Slope
+        self.effective_block_type == BlockType::Solid
+            || (self.effective_block_type == BlockType::Slope
                 && (slope_type == SlopeType::Square || slope_type == SlopeType::SquareDuplicate1))
     }
 }
 
+/// A tile of the optional layer 2, the background drawn behind the level.
+///
+/// Layer 2 uses the same level words as layer 1, but only the tile and flip
+/// mean anything, the block type bits are kept as they are.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Layer2Tile {
+    x: u16,
+    y: u16,
+    level_word: u16,
+}
+
+impl Layer2Tile {
+    pub fn from_level_word(level_word: u16) -> Self {
+        Layer2Tile {
+            x: 0,
+            y: 0,
+            level_word,
+        }
+    }
+
+    pub fn x(&self) -> u16 {
+        self.x
+    }
+
+    pub fn y(&self) -> u16 {
+        self.y
+    }
+
+    pub fn tile(&self) -> u16 {
+        self.level_word & LEVEL_TILE_MASK
+    }
+
+    pub fn flip(&self) -> Flip {
+        Flip::from(((self.level_word & LEVEL_FLIP_MASK) >> 10) as u8)
+    }
+
+    pub fn level_word(&self) -> u16 {
+        self.level_word
+    }
+}
+
 pub struct CellNeighbors {
     pub left: Option<usize>,
     pub right: Option<usize>,
@@ -571,7 +1382,8 @@ pub struct CellNeighbors {
 
 impl CellNeighbors {}
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SlopeType {
     HalfSolidH = 0x0,
     HalfSolidV,
@@ -648,11 +1460,394 @@ impl From<u8> for SlopeType {
     }
 }
 
-#[derive(Debug, PartialEq, Copy, Clone)]
-enum TreatAsSlopeType {
+impl SlopeType {
+    /// Every slope type except `SlopeType::None`, in BTS order.
+    pub const ALL: [SlopeType; 32] = [
+        SlopeType::HalfSolidH,
+        SlopeType::HalfSolidV,
+        SlopeType::QuarterSolid,
+        SlopeType::StairBigSteps,
+        SlopeType::FullSolidUnused,
+        SlopeType::SmallTriangle,
+        SlopeType::BigTriangle,
+        SlopeType::HalfPlat,
+        SlopeType::SquareDuplicate1,
+        SlopeType::SquareDuplicate2,
+        SlopeType::SquareDuplicate3,
+        SlopeType::SquareDuplicate4,
+        SlopeType::SquareDuplicate5,
+        SlopeType::SquareDuplicate6,
+        SlopeType::StairSmallSteps,
+        SlopeType::ConcaveTriangle,
+        SlopeType::HorizontalLines,
+        SlopeType::VerticalLines,
+        SlopeType::Slope45,
+        SlopeType::Square,
+        SlopeType::HillPart1,
+        SlopeType::HillPart2,
+        SlopeType::SmoothHillPart1,
+        SlopeType::SmoothHillPart2,
+        SlopeType::SmootherHillPart1,
+        SlopeType::SmootherHillPart2,
+        SlopeType::SmootherHillPart3,
+        SlopeType::SteepHillPart1,
+        SlopeType::SteepHillPart2,
+        SlopeType::SteeperHillPart1,
+        SlopeType::SteeperHillPart2,
+        SlopeType::SteeperHillPart3,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SlopeType::HalfSolidH => "half_solid_h",
+            SlopeType::HalfSolidV => "half_solid_v",
+            SlopeType::QuarterSolid => "quarter_solid",
+            SlopeType::StairBigSteps => "stair_big_steps",
+            SlopeType::FullSolidUnused => "full_solid_unused",
+            SlopeType::SmallTriangle => "small_triangle",
+            SlopeType::BigTriangle => "big_triangle",
+            SlopeType::HalfPlat => "half_plat",
+            SlopeType::SquareDuplicate1 => "square_duplicate_1",
+            SlopeType::SquareDuplicate2 => "square_duplicate_2",
+            SlopeType::SquareDuplicate3 => "square_duplicate_3",
+            SlopeType::SquareDuplicate4 => "square_duplicate_4",
+            SlopeType::SquareDuplicate5 => "square_duplicate_5",
+            SlopeType::SquareDuplicate6 => "square_duplicate_6",
+            SlopeType::StairSmallSteps => "stair_small_steps",
+            SlopeType::ConcaveTriangle => "concave_triangle",
+            SlopeType::HorizontalLines => "horizontal_lines",
+            SlopeType::VerticalLines => "vertical_lines",
+            SlopeType::Slope45 => "slope_45",
+            SlopeType::Square => "square",
+            SlopeType::HillPart1 => "hill_part_1",
+            SlopeType::HillPart2 => "hill_part_2",
+            SlopeType::SmoothHillPart1 => "smooth_hill_part_1",
+            SlopeType::SmoothHillPart2 => "smooth_hill_part_2",
+            SlopeType::SmootherHillPart1 => "smoother_hill_part_1",
+            SlopeType::SmootherHillPart2 => "smoother_hill_part_2",
+            SlopeType::SmootherHillPart3 => "smoother_hill_part_3",
+            SlopeType::SteepHillPart1 => "steep_hill_part_1",
+            SlopeType::SteepHillPart2 => "steep_hill_part_2",
+            SlopeType::SteeperHillPart1 => "steeper_hill_part_1",
+            SlopeType::SteeperHillPart2 => "steeper_hill_part_2",
+            SlopeType::SteeperHillPart3 => "steeper_hill_part_3",
+            SlopeType::None => "none",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TreatAsSlopeType {
     Solid = 0x0,
     SlopeRight,
     SlopeLeft,
     SlopeProtectNegX,
     SlopeProtectPosX,
 }
+
+impl TreatAsSlopeType {
+    pub const ALL: [TreatAsSlopeType; 5] = [
+        TreatAsSlopeType::Solid,
+        TreatAsSlopeType::SlopeRight,
+        TreatAsSlopeType::SlopeLeft,
+        TreatAsSlopeType::SlopeProtectNegX,
+        TreatAsSlopeType::SlopeProtectPosX,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TreatAsSlopeType::Solid => "solid",
+            TreatAsSlopeType::SlopeRight => "slope_right",
+            TreatAsSlopeType::SlopeLeft => "slope_left",
+            TreatAsSlopeType::SlopeProtectNegX => "slope_protect_neg_x",
+            TreatAsSlopeType::SlopeProtectPosX => "slope_protect_pos_x",
+        }
+    }
+}
+
+/// The bytes of a single screen room, every cell built from the given level
+/// word and bts.
+#[cfg(test)]
+pub(crate) fn room_bytes(cell: impl Fn(usize, usize) -> (u16, u8)) -> Vec<u8> {
+    let mut bytes = vec![0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    let cells: Vec<(u16, u8)> = (0..256).map(|i| cell(i % 16, i / 16)).collect();
+    for (word, _) in &cells {
+        bytes.extend_from_slice(&word.to_le_bytes());
+    }
+    bytes.extend(cells.iter().map(|(_, bts)| bts));
+    bytes
+}
+
+/// A single screen room, every cell built from the given level word and bts.
+#[cfg(test)]
+pub(crate) fn room_from(cell: impl Fn(usize, usize) -> (u16, u8)) -> Room {
+    Room::try_from_bytes(&room_bytes(cell)).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn area_is_parsed_by_name() {
+        assert_eq!("crateria".parse(), Ok(AreaIndex::Crateria));
+        assert_eq!("Wrecked Ship".parse(), Ok(AreaIndex::WreckedShip));
+        assert_eq!("wrecked-ship".parse(), Ok(AreaIndex::WreckedShip));
+        assert_eq!("TOURIAN".parse(), Ok(AreaIndex::Tourian));
+    }
+
+    #[test]
+    fn area_is_parsed_by_index() {
+        assert_eq!("0".parse(), Ok(AreaIndex::Crateria));
+        assert_eq!("5".parse(), Ok(AreaIndex::Tourian));
+        assert_eq!("0x4".parse(), Ok(AreaIndex::Maridia));
+        assert_eq!("0X04".parse(), Ok(AreaIndex::Maridia));
+    }
+
+    #[test]
+    fn unknown_areas_are_told_apart_by_name() {
+        assert_eq!(AreaIndex::Maridia.to_string(), "maridia");
+        assert_eq!(AreaIndex::from(0x12).to_string(), "unknown_12");
+        assert_ne!(
+            AreaIndex::from(0x09).to_string(),
+            AreaIndex::from(0x12).to_string()
+        );
+    }
+
+    #[test]
+    fn invalid_area_is_an_error() {
+        for input in ["", "zebes", "8", "0x", "0x10", "1.5", "256"] {
+            assert!(input.parse::<AreaIndex>().is_err(), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn short_header_is_an_error() {
+        assert_eq!(
+            Room::try_from_bytes(&[0; 14]).unwrap_err(),
+            RoomParseError::ShortHeader {
+                len: 14,
+                expected: ROOM_HEADER_SIZE
+            }
+        );
+    }
+
+    #[test]
+    fn zero_sized_room_is_an_error() {
+        let mut bytes = room_bytes(|_, _| (0, 0));
+        bytes[5] = 0;
+        assert_eq!(
+            Room::try_from_bytes(&bytes).unwrap_err(),
+            RoomParseError::ZeroSized {
+                width: 1,
+                height: 0
+            }
+        );
+    }
+
+    #[test]
+    fn truncated_level_data_is_an_error() {
+        let bytes = room_bytes(|_, _| (0, 0));
+        assert_eq!(
+            Room::try_from_bytes(&bytes[..ROOM_HEADER_SIZE + 100]).unwrap_err(),
+            RoomParseError::TruncatedLevelData {
+                offset: ROOM_HEADER_SIZE,
+                expected_end: ROOM_HEADER_SIZE + 512,
+                actual_end: ROOM_HEADER_SIZE + 100,
+            }
+        );
+    }
+
+    #[test]
+    fn truncated_bts_data_is_an_error() {
+        let bytes = room_bytes(|_, _| (0, 0));
+        assert_eq!(
+            Room::try_from_bytes(&bytes[..bytes.len() - 1]).unwrap_err(),
+            RoomParseError::TruncatedBtsData {
+                offset: ROOM_HEADER_SIZE + 512,
+                expected_end: ROOM_HEADER_SIZE + 768,
+                actual_end: ROOM_HEADER_SIZE + 767,
+            }
+        );
+    }
+
+    #[test]
+    fn partial_layer2_is_an_error() {
+        let mut bytes = room_bytes(|_, _| (0, 0));
+        bytes.extend([1, 2, 3]);
+        assert_eq!(
+            Room::try_from_bytes(&bytes).unwrap_err(),
+            RoomParseError::TrailingDataMismatch {
+                offset: ROOM_HEADER_SIZE + 768,
+                len: 3,
+                expected: 512,
+            }
+        );
+
+        // data after a full layer 2 block isn't padding either
+        let mut bytes = room_bytes(|_, _| (0, 0));
+        bytes.extend([0; 512]);
+        bytes.push(1);
+        assert!(matches!(
+            Room::try_from_bytes(&bytes),
+            Err(RoomParseError::TrailingDataMismatch { len: 513, .. })
+        ));
+    }
+
+    #[test]
+    fn zero_padding_is_ignored() {
+        let bytes = room_bytes(|x, _| (x as u16, 0));
+
+        let mut padded = bytes.clone();
+        padded.extend([0; 7]);
+        let room = Room::try_from_bytes(&padded).unwrap();
+        assert!(!room.has_layer2());
+        assert_eq!(room.to_bytes(), bytes);
+
+        // padding as long as a layer 2 block is still padding
+        let mut padded = bytes.clone();
+        padded.extend([0; 512]);
+        let room = Room::try_from_bytes(&padded).unwrap();
+        assert!(!room.has_layer2());
+        assert_eq!(room.to_bytes(), bytes);
+
+        let mut padded = bytes.clone();
+        padded.extend([0x05; 512]);
+        padded.extend([0; 600]);
+        let room = Room::try_from_bytes(&padded).unwrap();
+        assert_eq!(room.layer2.len(), 256);
+        assert_eq!(room.to_bytes(), padded[..bytes.len() + 512]);
+    }
+
+    #[test]
+    fn compressed_room_needs_the_exact_header() {
+        let bytes = room_bytes(|x, y| ((x * y) as u16, y as u8));
+        let compressed = Room::try_from_bytes(&bytes)
+            .unwrap()
+            .compressed_level_data();
+
+        let room = Room::try_from_compressed(&bytes[..ROOM_HEADER_SIZE], &compressed).unwrap();
+        assert_eq!(room.to_bytes(), bytes);
+
+        assert_eq!(
+            Room::try_from_compressed(&bytes[..ROOM_HEADER_SIZE - 1], &compressed).unwrap_err(),
+            RoomParseError::ShortHeader {
+                len: ROOM_HEADER_SIZE - 1,
+                expected: ROOM_HEADER_SIZE
+            }
+        );
+        assert_eq!(
+            Room::try_from_compressed(&bytes[..ROOM_HEADER_SIZE + 1], &compressed).unwrap_err(),
+            RoomParseError::LongHeader {
+                len: ROOM_HEADER_SIZE + 1,
+                expected: ROOM_HEADER_SIZE
+            }
+        );
+    }
+
+    #[test]
+    fn level_word_is_decoded() {
+        // spike, vertical flip, tile 0x2A5
+        let cell = Cell::from_level_word(0xAAA5);
+        assert_eq!(cell.block_type, BlockType::Spike);
+        assert_eq!(cell.flip(), Flip::Vertical);
+        assert!(cell.is_flipped_vertically());
+        assert!(!cell.is_flipped_horizontally());
+        assert_eq!(cell.tile(), 0x2A5);
+        assert_eq!(cell.level_word(), 0xAAA5);
+
+        // the tile keeps all 10 bits and the flips don't bleed into it
+        let cell = Cell::from_level_word(0x8FFF);
+        assert_eq!(cell.block_type, BlockType::Solid);
+        assert_eq!(cell.flip(), Flip::Both);
+        assert_eq!(cell.tile(), 0x3FF);
+
+        let cell = Cell::from_level_word(0x1400);
+        assert_eq!(cell.block_type, BlockType::Slope);
+        assert_eq!(cell.flip(), Flip::Horizontal);
+        assert!(cell.is_flipped_horizontally());
+        assert_eq!(cell.tile(), 0);
+    }
+
+    #[test]
+    fn copy_chains_resolve_to_their_source() {
+        let room = room_from(|x, y| match (x, y) {
+            // a horizontally flipped 45 degree slope
+            (5, 5) => (0x1000, 0x52),
+            // one block to the left, then one row up
+            (6, 5) => (0x5000, 0xFF),
+            (6, 6) => (0xD000, 0xFF),
+            // two blocks to the right
+            (0, 8) => (0x5000, 0x02),
+            (2, 8) => (0x8000, 0),
+            _ => (0, 0),
+        });
+
+        for (x, y) in [(6, 5), (6, 6)] {
+            let cell = room.get_cell(x, y).unwrap();
+            assert_eq!(cell.effective_block_type(), BlockType::Slope);
+            assert_eq!(cell.effective_bts(), 0x52);
+            assert_eq!(cell.get_slope_type(), SlopeType::Slope45);
+            assert_eq!(cell.get_slope_flip(), Flip::Horizontal);
+        }
+        assert_eq!(room.get_cell(6, 6).unwrap().block_type, BlockType::VCopy);
+
+        let cell = room.get_cell(0, 8).unwrap();
+        assert_eq!(cell.effective_block_type(), BlockType::Solid);
+        assert!(cell.is_square());
+    }
+
+    #[test]
+    fn broken_copy_chains_are_air() {
+        let room = room_from(|x, y| match (x, y) {
+            // two copies pointing at each other
+            (1, 1) => (0x5000, 0x01),
+            (2, 1) => (0x5000, 0xFF),
+            // before the first cell and below the last row
+            (0, 0) => (0x5000, 0xFF),
+            (3, 15) => (0xD000, 0x01),
+            _ => (0x8000, 0x13),
+        });
+
+        for (x, y) in [(1, 1), (2, 1), (0, 0), (3, 15)] {
+            let cell = room.get_cell(x, y).unwrap();
+            assert_eq!(cell.effective_block_type(), BlockType::Air, "{x}, {y}");
+            assert_eq!(cell.effective_bts(), 0, "{x}, {y}");
+        }
+    }
+
+    #[test]
+    fn scaled_slopes_stay_smooth() {
+        // a 45 degree slope going up to the right onto a floor
+        let room = room_from(|x, y| match (x, y) {
+            (4, 10) => (0x1000, 0x12),
+            (5.., 10) | (_, 11..) => (0x8000, 0),
+            _ => (0, 0),
+        });
+
+        let style = RenderStyle::default();
+        let img = room.render_image_scaled(&style, Scale::Pixels(4));
+        assert_eq!(
+            img.dimensions(),
+            Scale::Pixels(4).apply(256, 256),
+            "rendered at the scaled size"
+        );
+
+        // the left end of the slope moves one pixel per row, not one cell pixel per 4 rows
+        let left_end = |y: u32| (64..img.width()).find(|x| img.get_pixel(*x, y)[3] != 0);
+        let ends: Vec<u32> = (640..704).filter_map(left_end).collect();
+        assert_eq!(ends.len(), 64);
+        assert!(
+            ends.windows(2).all(|pair| pair[0] == pair[1] + 1),
+            "{:?}",
+            ends
+        );
+
+        // at the normal size the scaled renderer draws the same image
+        assert_eq!(
+            room.render_image_scaled(&style, Scale::Pixels(1)),
+            room.render_image_with_style(&style)
+        );
+    }
+}
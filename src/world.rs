@@ -0,0 +1,239 @@
+use std::path::Path;
+
+use image::{imageops, ImageResult, Rgba, RgbaImage};
+use imageproc::{
+    drawing::{draw_filled_rect_mut, draw_hollow_rect_mut},
+    rect::Rect,
+};
+
+use crate::{
+    constants::SCREEN_SIZE,
+    render::{draw_text, text_size, RenderStyle, Scale},
+    types::{AreaIndex, Room},
+};
+
+const OUTLINE_COLOR: Rgba<u8> = Rgba([255, 255, 0, 255]);
+const LABEL_COLOR: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const LABEL_BACKGROUND: Rgba<u8> = Rgba([0, 0, 0, 255]);
+const LABEL_SCALE: u32 = 4;
+
+/// The rooms of one area placed at their map position.
+#[derive(Debug, Clone)]
+pub struct AreaMap {
+    pub area: AreaIndex,
+    /// Map position of the top left corner of the image, in screens.
+    pub origin: (u8, u8),
+    pub image: RgbaImage,
+}
+
+impl AreaMap {
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> ImageResult<()> {
+        let path = path.as_ref();
+
+        // check if the folder exists
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+
+        self.image.save(path)
+    }
+}
+
+/// Renders the collision of every room of `area` at its `map_x` and `map_y`.
+///
+/// The image only covers the screens used by the rooms, every room is drawn
+/// at `scale` instead of scaling the whole map afterwards. Every room gets an
+/// outline and a label with its `room_id`, or its room index in hex when the
+/// id is empty; labels are left out when the map is too small to read them.
+/// Returns `None` when no room belongs to the area.
+pub fn render_area(
+    rooms: &[Room],
+    area: AreaIndex,
+    style: &RenderStyle,
+    scale: Scale,
+) -> Option<AreaMap> {
+    let rooms: Vec<&Room> = rooms
+        .iter()
+        .filter(|room| room.area_index() == area)
+        .collect();
+
+    let min_x = rooms.iter().map(|room| room.map_x()).min()?;
+    let min_y = rooms.iter().map(|room| room.map_y()).min()?;
+    let max_x = rooms
+        .iter()
+        .map(|room| room.map_x() as u32 + room.room_width() as u32)
+        .max()?;
+    let max_y = rooms
+        .iter()
+        .map(|room| room.map_y() as u32 + room.room_height() as u32)
+        .max()?;
+
+    let (width, height) = scale.apply(
+        (max_x - min_x as u32) * SCREEN_SIZE,
+        (max_y - min_y as u32) * SCREEN_SIZE,
+    );
+    let mut image = RgbaImage::new(width, height);
+
+    // the corners are scaled instead of the sizes, so neighbouring rooms
+    // still line up when a thumbnail rounds their sizes
+    let bounds = |room: &Room| {
+        let x = (room.map_x() - min_x) as u32 * SCREEN_SIZE;
+        let y = (room.map_y() - min_y) as u32 * SCREEN_SIZE;
+        let left = scale.apply_offset(x);
+        let top = scale.apply_offset(y);
+        let right = scale.apply_offset(x + room.room_width() as u32 * SCREEN_SIZE);
+        let bottom = scale.apply_offset(y + room.room_height() as u32 * SCREEN_SIZE);
+        (left, top, (right - left).max(1), (bottom - top).max(1))
+    };
+
+    for room in &rooms {
+        let (x, y, width, height) = bounds(room);
+        let room_image = match scale {
+            Scale::Pixels(_) => room.render_image_scaled(style, scale),
+            Scale::TilesPerPixel(_) => {
+                imageops::thumbnail(&room.render_image_with_style(style), width, height)
+            }
+        };
+        imageops::overlay(&mut image, &room_image, x as i64, y as i64);
+    }
+
+    let thickness = scale.apply_offset(2).max(1);
+    let label_scale = scale.apply_offset(LABEL_SCALE);
+    let label_offset = scale.apply_offset(4);
+
+    // outlines and labels go on top of every room, so overlapping rooms can't hide them
+    for room in &rooms {
+        let (x, y, width, height) = bounds(room);
+        for inset in (0..thickness).take_while(|inset| 2 * inset < width.min(height)) {
+            draw_hollow_rect_mut(
+                &mut image,
+                Rect::at((x + inset) as i32, (y + inset) as i32)
+                    .of_size(width - 2 * inset, height - 2 * inset),
+                OUTLINE_COLOR,
+            );
+        }
+
+        if label_scale == 0 {
+            continue;
+        }
+
+        let label = if room.room_id.is_empty() {
+            format!("{:02X}", room.room_index())
+        } else {
+            room.room_id.clone()
+        };
+        let (label_width, label_height) = text_size(&label, label_scale);
+        let padding = label_scale;
+        draw_filled_rect_mut(
+            &mut image,
+            Rect::at((x + label_offset) as i32, (y + label_offset) as i32)
+                .of_size(label_width + 2 * padding, label_height + 2 * padding),
+            LABEL_BACKGROUND,
+        );
+        draw_text(
+            &mut image,
+            x + label_offset + padding,
+            y + label_offset + padding,
+            &label,
+            label_scale,
+            LABEL_COLOR,
+        );
+    }
+
+    Some(AreaMap {
+        area,
+        origin: (min_x, min_y),
+        image,
+    })
+}
+
+/// Every area with at least one room, unknown areas included, by area index.
+pub fn areas(rooms: &[Room]) -> Vec<AreaIndex> {
+    let mut areas: Vec<AreaIndex> = rooms.iter().map(|room| room.area_index()).collect();
+    areas.sort_by_key(|area| area.index());
+    areas.dedup();
+    areas
+}
+
+/// `render_area` for every area in `areas`.
+pub fn render_world(rooms: &[Room], style: &RenderStyle, scale: Scale) -> Vec<AreaMap> {
+    areas(rooms)
+        .into_iter()
+        .filter_map(|area| render_area(rooms, area, style, scale))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // an empty room of the given size in screens at the given map position
+    fn room_at(area: u8, map_x: u8, map_y: u8, width: u8, height: u8) -> Room {
+        let mut bytes = vec![
+            area, 0, map_x, map_y, width, height, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        bytes.resize(bytes.len() + width as usize * height as usize * 256 * 3, 0);
+        Room::try_from_bytes(&bytes).unwrap()
+    }
+
+    #[test]
+    fn area_map_covers_every_room_of_the_area() {
+        let rooms = [
+            room_at(1, 3, 4, 2, 1),
+            room_at(1, 5, 6, 1, 1),
+            room_at(2, 0, 0, 1, 1),
+        ];
+
+        let style = RenderStyle::default();
+        let map = render_area(&rooms, AreaIndex::Brinstar, &style, Scale::default()).unwrap();
+        assert_eq!(map.origin, (3, 4));
+        assert_eq!(map.image.dimensions(), (3 * SCREEN_SIZE, 3 * SCREEN_SIZE));
+
+        assert_eq!(render_world(&rooms, &style, Scale::default()).len(), 2);
+        assert_eq!(areas(&rooms), [AreaIndex::Brinstar, AreaIndex::Norfair]);
+        assert!(render_area(&rooms, AreaIndex::Maridia, &style, Scale::default()).is_none());
+    }
+
+    #[test]
+    fn rooms_of_unknown_areas_get_their_own_map() {
+        let rooms = [
+            room_at(0x12, 0, 0, 1, 1),
+            room_at(0, 0, 0, 1, 1),
+            room_at(0x09, 2, 0, 1, 1),
+            room_at(0x12, 1, 0, 1, 1),
+        ];
+
+        assert_eq!(
+            areas(&rooms),
+            [
+                AreaIndex::Crateria,
+                AreaIndex::Unknown(0x09),
+                AreaIndex::Unknown(0x12)
+            ]
+        );
+        let maps = render_world(&rooms, &RenderStyle::default(), Scale::default());
+        assert_eq!(maps.len(), 3);
+        assert_eq!(maps[2].area, AreaIndex::Unknown(0x12));
+        assert_eq!(maps[2].image.dimensions(), (2 * SCREEN_SIZE, SCREEN_SIZE));
+    }
+
+    #[test]
+    fn area_map_is_rendered_at_the_scale() {
+        let rooms = [room_at(1, 3, 4, 2, 1), room_at(1, 5, 6, 1, 1)];
+        let style = RenderStyle::default();
+
+        for scale in [
+            Scale::Pixels(2),
+            Scale::TilesPerPixel(1.0),
+            Scale::TilesPerPixel(3.0),
+        ] {
+            let map = render_area(&rooms, AreaIndex::Brinstar, &style, scale).unwrap();
+            assert_eq!(
+                map.image.dimensions(),
+                scale.apply(3 * SCREEN_SIZE, 3 * SCREEN_SIZE)
+            );
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::path::PathBuf;
+
+use mamamia::{constants::ROOM_HEADER_SIZE, AreaIndex, Room};
+
+// set MAMAMIA_FIXTURES to run the round trip over a directory of extracted rooms
+fn fixture_dir() -> PathBuf {
+    match std::env::var_os("MAMAMIA_FIXTURES") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures"),
+    }
+}
+
+fn fixture_paths() -> Vec<PathBuf> {
+    let dir = fixture_dir();
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("cannot read {}: {}", dir.display(), e))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "room"))
+        .collect();
+    paths.sort();
+
+    assert!(!paths.is_empty(), "no .room files in {}", dir.display());
+    paths
+}
+
+#[test]
+fn room_round_trip() {
+    for path in fixture_paths() {
+        let bytes = std::fs::read(&path).unwrap();
+        let room =
+            Room::try_from_bytes(&bytes).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+
+        let written = room.to_bytes();
+        assert_eq!(written.len(), bytes.len(), "{}", path.display());
+        if let Some(offset) = written.iter().zip(&bytes).position(|(a, b)| a != b) {
+            panic!("{}: first difference at {:#X}", path.display(), offset);
+        }
+    }
+}
+
+#[test]
+fn unknown_area_round_trip() {
+    for path in fixture_paths() {
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[0] = 0x2A;
+        let room = Room::try_from_bytes(&bytes).unwrap();
+
+        assert_eq!(room.area_index(), AreaIndex::Unknown(0x2A));
+        assert_eq!(room.to_bytes(), bytes, "{}", path.display());
+    }
+}
+
+#[test]
+fn compressed_round_trip() {
+    for path in fixture_paths() {
+        let bytes = std::fs::read(&path).unwrap();
+        let room = Room::try_from_bytes(&bytes).unwrap();
+
+        let parsed =
+            Room::try_from_compressed(&bytes[..ROOM_HEADER_SIZE], &room.compressed_level_data())
+                .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        assert_eq!(parsed.to_bytes(), bytes, "{}", path.display());
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json_round_trip() {
+    for path in fixture_paths() {
+        let bytes = std::fs::read(&path).unwrap();
+        let mut room = Room::try_from_bytes(&bytes).unwrap();
+        room.room_id = path.display().to_string();
+
+        let json = serde_json::to_string(&room).unwrap();
+        let parsed: Room =
+            serde_json::from_str(&json).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        assert_eq!(parsed.room_id, room.room_id);
+        assert_eq!(parsed.to_bytes(), bytes, "{}", path.display());
+        assert_eq!(parsed.slope_runs, room.slope_runs, "{}", path.display());
+    }
+}